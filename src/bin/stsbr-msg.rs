@@ -0,0 +1,86 @@
+//! Sends a command to a running stsbr through its control socket, e.g. from a
+//! key binding: `stsbr-msg action volume volume_up`.
+
+#[path = "../socket_path.rs"]
+mod socket_path;
+
+use socket_path::default_socket_path;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: stsbr-msg [OPTIONS] <COMMAND>
+
+Commands:
+    refresh [BLOCK]        Refresh one block, or all of them
+    action BLOCK ACTION    Run an action of a block, e.g. `action volume volume_up`
+    query                  Print the state of every block as JSON
+
+Options:
+    -s, --socket <PATH>    Control socket of the bar
+    -h, --help             Print this help and exit
+";
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let mut socket = None;
+
+    while let Some(arg) = args.peek() {
+        match arg.as_str() {
+            "-s" | "--socket" => {
+                args.next();
+                socket = args.next().map(PathBuf::from);
+
+                if socket.is_none() {
+                    fail("missing value for --socket");
+                }
+            }
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return;
+            }
+            _ => break,
+        }
+    }
+
+    let request: Vec<String> = args.collect();
+
+    if request.is_empty() {
+        fail("missing command");
+    }
+
+    let socket = socket
+        .or_else(default_socket_path)
+        .unwrap_or_else(|| fail("XDG_RUNTIME_DIR is not set, pass --socket"));
+    let reply = send(&socket, &request.join(" ")).unwrap_or_else(|e| {
+        eprintln!("stsbr-msg: cannot talk to {}: {}", socket.display(), e);
+        exit(1);
+    });
+
+    match reply.strip_prefix("error: ") {
+        Some(error) => {
+            eprintln!("stsbr-msg: {}", error);
+            exit(1);
+        }
+        None if reply != "ok" => println!("{}", reply),
+        None => {}
+    }
+}
+
+fn send(socket: &Path, request: &str) -> std::io::Result<String> {
+    let stream = UnixStream::connect(socket)?;
+    writeln!(&stream, "{}", request)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+
+    Ok(reply.trim_end().to_owned())
+}
+
+fn fail(message: &str) -> ! {
+    eprint!("stsbr-msg: {}\n\n{}", message, USAGE);
+    exit(2);
+}
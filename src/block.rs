@@ -1,9 +1,14 @@
-use std::char;
+use crate::icons;
+use crate::markup::Markup;
+use crate::scheduler::Message;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BlockError(String);
 
 impl Error for BlockError {}
@@ -23,85 +28,460 @@ impl BlockError {
 pub trait Block {
     fn current_state(&mut self) -> Result<BlockState, BlockError>;
     fn handle_click(&self, event: ClickEvent);
+
+    /// How often the block should be polled. Blocks returning `None` are only
+    /// refreshed when they push an update through their `Notifier`.
+    fn refresh_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(5))
+    }
+
+    /// Names of the actions `run_action` understands, which can be bound to
+    /// clicks in the config.
+    fn actions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn run_action(&self, _action: &str) {}
+
+    /// Called when the bar gets hidden. Blocks doing work in the background
+    /// should suspend it until `resume` is called.
+    fn pause(&mut self) {}
+
+    fn resume(&mut self) {}
+}
+
+/// Handle through which a block tells the bar that its state has changed.
+#[derive(Clone)]
+pub struct Notifier {
+    sender: Sender<Message>,
+    block: String,
+}
+
+impl Notifier {
+    pub fn new(sender: Sender<Message>, block: String) -> Self {
+        Notifier { sender, block }
+    }
+
+    /// The name of the block this notifier belongs to.
+    pub fn block_name(&self) -> &str {
+        &self.block
+    }
+
+    pub fn notify(&self) {
+        if self
+            .sender
+            .send(Message::Update(self.block.clone()))
+            .is_err()
+        {
+            warn!("Bar is gone, dropping update of block {}", self.block);
+        }
+    }
+}
+
+/// How much attention a block needs, from nothing to report to something
+/// being wrong. The theme decides what each level looks like.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Idle,
+    Info,
+    Good,
+    Warning,
+    Critical,
+}
+
+/// Values at which a measurement turns into a warning or becomes critical.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Thresholds {
+    warning: Option<f64>,
+    critical: Option<f64>,
+}
+
+impl Thresholds {
+    pub fn new(warning: Option<f64>, critical: Option<f64>) -> Self {
+        Thresholds { warning, critical }
+    }
+
+    /// Severity of a value that is worse the higher it gets, like load.
+    pub fn above(&self, value: f64) -> Severity {
+        self.classify(|threshold| value >= threshold)
+    }
+
+    /// Severity of a value that is worse the lower it gets, like free space.
+    pub fn below(&self, value: f64) -> Severity {
+        self.classify(|threshold| value <= threshold)
+    }
+
+    fn classify<F: Fn(f64) -> bool>(&self, reached: F) -> Severity {
+        if self.critical.is_some_and(&reached) {
+            Severity::Critical
+        } else if self.warning.is_some_and(&reached) {
+            Severity::Warning
+        } else {
+            Severity::Idle
+        }
+    }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Either pixels, or a text whose width is used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MinWidth {
+    Pixels(u32),
+    Text(String),
+}
+
+/// The `min_width` and `align` keys of a block section, which keep a block
+/// from changing its width with its text.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Layout {
+    #[serde(default)]
+    min_width: Option<MinWidth>,
+    #[serde(default)]
+    align: Option<Align>,
+}
+
+impl Layout {
+    /// Sets what the block itself left unset.
+    pub fn apply(&self, mut state: BlockState) -> BlockState {
+        if let (None, Some(min_width)) = (&state.min_width, &self.min_width) {
+            state = state.with_min_width(min_width.clone());
+        }
+
+        if let (None, Some(align)) = (state.align, self.align) {
+            state = state.with_align(align);
+        }
+
+        state
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct BlockState {
-    text: String,
+    text: Markup,
+    short_text: Option<Markup>,
+    color: Option<String>,
+    background: Option<String>,
+    border: Option<String>,
+    min_width: Option<MinWidth>,
+    align: Option<Align>,
+    urgent: Option<bool>,
+    separator: Option<bool>,
+    separator_block_width: Option<u32>,
+    severity: Severity,
 }
 
 impl BlockState {
+    /// A state showing plain text, which is escaped.
     pub fn new(text: String) -> BlockState {
-        BlockState { text }
+        BlockState::from_markup(Markup::text(&text))
+    }
+
+    pub fn from_markup(text: Markup) -> BlockState {
+        BlockState {
+            text,
+            short_text: None,
+            color: None,
+            background: None,
+            border: None,
+            min_width: None,
+            align: None,
+            urgent: None,
+            separator: None,
+            separator_block_width: None,
+            severity: Severity::Idle,
+        }
+    }
+
+    pub fn with_short_text(mut self, short_text: String) -> Self {
+        self.short_text = Some(Markup::text(&short_text));
+        self
+    }
+
+    pub fn with_short_markup(mut self, short_text: Markup) -> Self {
+        self.short_text = Some(short_text);
+        self
+    }
+
+    pub fn with_color(mut self, color: String) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_background(mut self, background: String) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    pub fn with_border(mut self, border: String) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    pub fn with_min_width(mut self, min_width: MinWidth) -> Self {
+        self.min_width = Some(min_width);
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = Some(align);
+        self
     }
 
-    pub fn text(&self) -> &String {
+    pub fn with_urgent(mut self, urgent: bool) -> Self {
+        self.urgent = Some(urgent);
+        self
+    }
+
+    pub fn with_separator(mut self, separator: bool) -> Self {
+        self.separator = Some(separator);
+        self
+    }
+
+    pub fn with_separator_block_width(mut self, width: u32) -> Self {
+        self.separator_block_width = Some(width);
+        self
+    }
+
+    /// Colors the block through the theme, unless colors are set explicitly.
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn text(&self) -> &Markup {
         &self.text
     }
+
+    pub fn short_text(&self) -> Option<&Markup> {
+        self.short_text.as_ref()
+    }
+
+    pub fn color(&self) -> Option<&String> {
+        self.color.as_ref()
+    }
+
+    pub fn background(&self) -> Option<&String> {
+        self.background.as_ref()
+    }
+
+    pub fn border(&self) -> Option<&String> {
+        self.border.as_ref()
+    }
+
+    pub fn min_width(&self) -> Option<&MinWidth> {
+        self.min_width.as_ref()
+    }
+
+    pub fn align(&self) -> Option<Align> {
+        self.align
+    }
+
+    pub fn urgent(&self) -> Option<bool> {
+        self.urgent
+    }
+
+    pub fn separator(&self) -> Option<bool> {
+        self.separator
+    }
+
+    pub fn separator_block_width(&self) -> Option<u32> {
+        self.separator_block_width
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum MouseButton {
     Left,
     Middle,
     Right,
     ScrollUp,
     ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    Back,
+    Forward,
+}
+
+impl MouseButton {
+    /// Maps an X11 button number, as used by i3bar and lemonbar, to a button.
+    pub fn from_code(code: u32) -> Option<MouseButton> {
+        match code {
+            1 => Some(MouseButton::Left),
+            2 => Some(MouseButton::Middle),
+            3 => Some(MouseButton::Right),
+            4 => Some(MouseButton::ScrollUp),
+            5 => Some(MouseButton::ScrollDown),
+            6 => Some(MouseButton::ScrollLeft),
+            7 => Some(MouseButton::ScrollRight),
+            8 => Some(MouseButton::Back),
+            9 => Some(MouseButton::Forward),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u32 {
+        match self {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::ScrollUp => 4,
+            MouseButton::ScrollDown => 5,
+            MouseButton::ScrollLeft => 6,
+            MouseButton::ScrollRight => 7,
+            MouseButton::Back => 8,
+            MouseButton::Forward => 9,
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-#[allow(dead_code)]
+/// A symbol shown in a block. What it looks like depends on the icon set
+/// chosen in the config.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Icon {
-    LightningBolt = 0xf0e7,
-    VolumeUp = 0xf028,
-    VolumeDown = 0xf027,
-    VolumeOff = 0xf026,
-    VolumeMute = 0xf6a9,
-    Globe = 0xf0ac,
-    Music = 0xf001,
-    Play = 0xf04b,
-    Pause = 0xf04c,
-    HDD = 0xf0a0,
-    Calendar = 0xf133,
+    LightningBolt,
+    VolumeUp,
+    VolumeDown,
+    VolumeOff,
+    VolumeMute,
+    Headphones,
+    Tv,
+    Microphone,
+    MicrophoneOff,
+    Globe,
+    Music,
+    Play,
+    Pause,
+    #[serde(rename = "hdd")]
+    HDD,
+    Calendar,
 }
 
 impl Display for Icon {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", char::from_u32(*self as u32).unwrap())
+        f.write_str(&icons::glyph(*self))
     }
 }
 
-#[derive(Debug)]
-pub struct Position(pub u32, pub u32);
-#[derive(Debug)]
+/// Named like i3bar reports them, which is also how they are configured.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+pub enum Modifier {
+    Shift,
+    Control,
+    Lock,
+    Mod1,
+    Mod2,
+    Mod3,
+    Mod4,
+    Mod5,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Position(pub i32, pub i32);
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Dimensions(pub u32, pub u32);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClickEvent {
     button: MouseButton,
+    instance: String,
+    name: Option<String>,
+    modifiers: Vec<Modifier>,
     position: Position,
+    relative_position: Position,
     block_dimensions: Dimensions,
-    instance: usize,
+    scale: f64,
 }
 
 impl ClickEvent {
-    pub fn new(
-        button: MouseButton,
-        position: Position,
-        block_dimensions: Dimensions,
-        instance: usize,
-    ) -> Self {
+    /// `instance` is the name of the block that was clicked.
+    pub fn new(button: MouseButton, instance: String) -> Self {
         ClickEvent {
             button,
-            position,
-            block_dimensions,
             instance,
+            name: None,
+            modifiers: vec![],
+            position: Position(0, 0),
+            relative_position: Position(0, 0),
+            block_dimensions: Dimensions(0, 0),
+            scale: 1.0,
         }
     }
 
-    pub fn instance(&self) -> usize {
-        self.instance
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn with_modifiers(mut self, modifiers: Vec<Modifier>) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_relative_position(mut self, relative_position: Position) -> Self {
+        self.relative_position = relative_position;
+        self
+    }
+
+    pub fn with_block_dimensions(mut self, block_dimensions: Dimensions) -> Self {
+        self.block_dimensions = block_dimensions;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
     }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
     pub fn button(&self) -> MouseButton {
         self.button
     }
+
+    pub fn name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    pub fn modifiers(&self) -> &[Modifier] {
+        &self.modifiers
+    }
+
+    /// Position of the pointer on the whole screen.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Position of the pointer relative to the top left corner of the block.
+    pub fn relative_position(&self) -> Position {
+        self.relative_position
+    }
+
+    pub fn block_dimensions(&self) -> Dimensions {
+        self.block_dimensions
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
 }
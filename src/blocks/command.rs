@@ -0,0 +1,409 @@
+use crate::block::{Block, BlockError, BlockState, ClickEvent, Notifier};
+use crate::markup::Markup;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use serde::Deserialize;
+use std::cell::Cell;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Exit status with which i3blocks scripts mark their block urgent.
+const URGENT_EXIT_CODE: i32 = 33;
+
+/// How long a command that is run per refresh may take before it is killed.
+const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Lines with the full text, short text, color and background.
+    #[default]
+    Raw,
+    /// One i3bar block as a JSON object.
+    Json,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkupMode {
+    #[default]
+    None,
+    Pango,
+}
+
+#[derive(Deserialize)]
+pub struct CommandConfig {
+    /// Run with `sh -c`.
+    command: String,
+    /// Passed to the command as `BLOCK_INSTANCE`.
+    #[serde(default)]
+    instance: Option<String>,
+    /// Keeps the command running and shows every line it prints, instead of
+    /// running it on every refresh. Clicks are written to its stdin as JSON.
+    #[serde(default)]
+    persistent: bool,
+    #[serde(default)]
+    output: OutputFormat,
+    /// Whether the command prints Pango markup rather than plain text.
+    #[serde(default)]
+    markup: MarkupMode,
+}
+
+#[derive(Deserialize)]
+struct JsonOutput {
+    full_text: String,
+    short_text: Option<String>,
+    color: Option<String>,
+    background: Option<String>,
+    border: Option<String>,
+    urgent: Option<bool>,
+    separator: Option<bool>,
+    separator_block_width: Option<u32>,
+}
+
+enum Runner {
+    /// Runs the command once per request, with the click that caused it.
+    Interval(Sender<Option<ClickEvent>>),
+    /// Clicks are written to the command by a thread of their own, as a
+    /// command that doesn't read them would block us once the pipe is full.
+    Persistent {
+        child: Child,
+        clicks: Sender<serde_json::Value>,
+    },
+}
+
+/// Shows the output of an external command, compatible with i3blocks scripts.
+pub struct CommandBlock {
+    runner: Runner,
+    states: Receiver<Result<BlockState, BlockError>>,
+    state: Option<Result<BlockState, BlockError>>,
+    /// Set when a run is requested, so that the refresh following it doesn't
+    /// request another one.
+    running: Cell<bool>,
+}
+
+impl Block for CommandBlock {
+    fn current_state(&mut self) -> Result<BlockState, BlockError> {
+        let mut updated = false;
+
+        for state in self.states.try_iter() {
+            self.state = Some(state);
+            self.running.set(false);
+            updated = true;
+        }
+
+        // A finished run asks for a refresh, which must not start it again.
+        if let Runner::Interval(requests) = &self.runner {
+            if !updated && !self.running.get() && requests.send(None).is_ok() {
+                self.running.set(true);
+            }
+        }
+
+        match &self.state {
+            Some(state) => state.clone(),
+            None => Err(BlockError::new("No output yet".into())),
+        }
+    }
+
+    fn handle_click(&self, event: ClickEvent) {
+        match &self.runner {
+            Runner::Interval(requests) => {
+                if requests.send(Some(event)).is_ok() {
+                    self.running.set(true);
+                } else {
+                    error!("Command thread is gone");
+                }
+            }
+            Runner::Persistent { clicks, .. } => {
+                if clicks.send(click_json(&event)).is_err() {
+                    warn!("Cannot pass click to command, it stopped reading them");
+                }
+            }
+        }
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        match self.runner {
+            Runner::Interval(_) => Some(Duration::from_secs(5)),
+            Runner::Persistent { .. } => None,
+        }
+    }
+}
+
+impl CommandBlock {
+    pub fn new(config: CommandConfig, notifier: Notifier) -> Result<Self, BlockError> {
+        let (state_sender, states) = std::sync::mpsc::channel();
+
+        let runner = if config.persistent {
+            spawn_persistent(config, state_sender, notifier)?
+        } else {
+            let (requests, request_receiver) = std::sync::mpsc::channel();
+
+            thread::spawn(move || {
+                // Ends once the block, and with it the sender, is dropped.
+                for click in request_receiver {
+                    let state = run_once(&config, notifier.block_name(), click);
+
+                    if state_sender.send(state).is_err() {
+                        break;
+                    }
+
+                    notifier.notify();
+                }
+            });
+
+            Runner::Interval(requests)
+        };
+
+        Ok(CommandBlock {
+            runner,
+            states,
+            state: None,
+            running: Cell::new(false),
+        })
+    }
+}
+
+impl Drop for CommandBlock {
+    fn drop(&mut self) {
+        if let Runner::Persistent { child, .. } = &mut self.runner {
+            // The reader thread stops at the end of the output.
+            kill_group(child.id());
+            let _ = child.wait();
+        }
+    }
+}
+
+fn shell_command(config: &CommandConfig, name: &str) -> Command {
+    let mut command = Command::new("sh");
+    // The bar sends its stop and continue signals, SIGUSR1 and SIGUSR2, to
+    // our whole process group, which would kill the command.
+    command
+        .arg("-c")
+        .arg(&config.command)
+        .env("BLOCK_NAME", name)
+        .process_group(0);
+
+    if let Some(instance) = &config.instance {
+        command.env("BLOCK_INSTANCE", instance);
+    }
+
+    command
+}
+
+fn run_once(
+    config: &CommandConfig,
+    name: &str,
+    click: Option<ClickEvent>,
+) -> Result<BlockState, BlockError> {
+    let mut command = shell_command(config, name);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(event) = click {
+        command
+            .env("BLOCK_BUTTON", event.button().code().to_string())
+            .env("BLOCK_MODIFIERS", modifier_names(&event).join(","))
+            .env("BLOCK_X", event.position().0.to_string())
+            .env("BLOCK_Y", event.position().1.to_string())
+            .env("BLOCK_RELATIVE_X", event.relative_position().0.to_string())
+            .env("BLOCK_RELATIVE_Y", event.relative_position().1.to_string())
+            .env("BLOCK_WIDTH", event.block_dimensions().0.to_string())
+            .env("BLOCK_HEIGHT", event.block_dimensions().1.to_string());
+    }
+
+    let child = command
+        .spawn()
+        .map_err(|e| BlockError::new(format!("Cannot run `{}`: {}", config.command, e)))?;
+    let (done, finished) = std::sync::mpsc::channel::<()>();
+    let leader = child.id();
+    let watchdog = thread::spawn(move || match finished.recv_timeout(RUN_TIMEOUT) {
+        Err(RecvTimeoutError::Timeout) => {
+            kill_group(leader);
+            true
+        }
+        _ => false,
+    });
+    let output = child.wait_with_output();
+    let _ = done.send(());
+
+    if watchdog.join().unwrap_or(false) {
+        return Err(BlockError::new(format!(
+            "`{}` took longer than {}s and was killed",
+            config.command,
+            RUN_TIMEOUT.as_secs()
+        )));
+    }
+
+    let output =
+        output.map_err(|e| BlockError::new(format!("Cannot run `{}`: {}", config.command, e)))?;
+    let urgent = output.status.code() == Some(URGENT_EXIT_CODE);
+
+    if !output.status.success() && !urgent {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut message = format!("`{}` failed with {}", config.command, output.status);
+
+        if !stderr.trim().is_empty() {
+            message = format!("{}: {}", message, stderr.trim());
+        }
+
+        return Err(BlockError::new(message));
+    }
+
+    let state = parse_output(&String::from_utf8_lossy(&output.stdout), config)?;
+
+    Ok(if urgent {
+        state.with_urgent(true)
+    } else {
+        state
+    })
+}
+
+fn spawn_persistent(
+    config: CommandConfig,
+    states: Sender<Result<BlockState, BlockError>>,
+    notifier: Notifier,
+) -> Result<Runner, BlockError> {
+    let mut child = shell_command(&config, notifier.block_name())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| BlockError::new(format!("Cannot run `{}`: {}", config.command, e)))?;
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take();
+    let (clicks, click_receiver) = std::sync::mpsc::channel::<serde_json::Value>();
+
+    thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            // Ends once the block is dropped or the command closes its stdin.
+            for click in click_receiver {
+                if let Err(e) = writeln!(stdin, "{}", click) {
+                    warn!("Cannot pass click to command: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    thread::spawn(move || {
+        if let Some(stdout) = stdout {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if states.send(parse_output(&line, &config)).is_err() {
+                    return;
+                }
+
+                notifier.notify();
+            }
+        }
+
+        let exited = Err(BlockError::new(format!("`{}` exited", config.command)));
+
+        if states.send(exited).is_ok() {
+            notifier.notify();
+        }
+    });
+
+    Ok(Runner::Persistent { child, clicks })
+}
+
+/// Sends SIGTERM to the process group a command leads, which also reaches
+/// whatever it started in the background.
+fn kill_group(leader: u32) {
+    let _ = kill(Pid::from_raw(-(leader as i32)), Signal::SIGTERM);
+}
+
+fn parse_output(output: &str, config: &CommandConfig) -> Result<BlockState, BlockError> {
+    let markup = |text: &str| match config.markup {
+        MarkupMode::None => Markup::text(text),
+        MarkupMode::Pango => Markup::trusted(text.to_owned()),
+    };
+
+    match config.output {
+        OutputFormat::Raw => {
+            let mut lines = output.lines();
+            let mut state = BlockState::from_markup(markup(lines.next().unwrap_or("")));
+
+            if let Some(short_text) = lines.next().filter(|line| !line.is_empty()) {
+                state = state.with_short_markup(markup(short_text));
+            }
+
+            if let Some(color) = lines.next().filter(|line| !line.is_empty()) {
+                state = state.with_color(color.to_owned());
+            }
+
+            if let Some(background) = lines.next().filter(|line| !line.is_empty()) {
+                state = state.with_background(background.to_owned());
+            }
+
+            Ok(state)
+        }
+        OutputFormat::Json => {
+            let json: JsonOutput = serde_json::from_str(output.trim()).map_err(|e| {
+                BlockError::new(format!("Invalid output of `{}`: {}", config.command, e))
+            })?;
+            let mut state = BlockState::from_markup(markup(&json.full_text));
+
+            if let Some(short_text) = json.short_text {
+                state = state.with_short_markup(markup(&short_text));
+            }
+
+            if let Some(color) = json.color {
+                state = state.with_color(color);
+            }
+
+            if let Some(background) = json.background {
+                state = state.with_background(background);
+            }
+
+            if let Some(border) = json.border {
+                state = state.with_border(border);
+            }
+
+            if let Some(urgent) = json.urgent {
+                state = state.with_urgent(urgent);
+            }
+
+            if let Some(separator) = json.separator {
+                state = state.with_separator(separator);
+            }
+
+            if let Some(width) = json.separator_block_width {
+                state = state.with_separator_block_width(width);
+            }
+
+            Ok(state)
+        }
+    }
+}
+
+/// A click the way i3bar reports it, for persistent commands.
+fn click_json(event: &ClickEvent) -> serde_json::Value {
+    serde_json::json!({
+        "name": event.name(),
+        "instance": event.instance(),
+        "button": event.button().code(),
+        "modifiers": modifier_names(event),
+        "x": event.position().0,
+        "y": event.position().1,
+        "relative_x": event.relative_position().0,
+        "relative_y": event.relative_position().1,
+        "width": event.block_dimensions().0,
+        "height": event.block_dimensions().1,
+        "scale": event.scale(),
+    })
+}
+
+/// Modifiers by the names i3bar uses, which are those of the variants.
+fn modifier_names(event: &ClickEvent) -> Vec<String> {
+    event
+        .modifiers()
+        .iter()
+        .map(|modifier| format!("{:?}", modifier))
+        .collect()
+}
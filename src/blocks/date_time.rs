@@ -1,24 +1,51 @@
 use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon};
+use crate::format::{Format, Values};
 use chrono::Local;
+use serde::Deserialize;
+use std::time::Duration;
 
-pub struct DateTime {}
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DateTimeConfig {
+    /// Knows `icon`, `date` and `time`.
+    format: Format,
+}
+
+impl Default for DateTimeConfig {
+    fn default() -> Self {
+        DateTimeConfig {
+            format: "{icon} {date} {time}".parse().unwrap(),
+        }
+    }
+}
+
+pub struct DateTime {
+    format: Format,
+}
 
 impl Block for DateTime {
     fn current_state(&mut self) -> Result<BlockState, BlockError> {
         let now: chrono::DateTime<Local> = Local::now();
 
-        Ok(BlockState::new(format!(
-            "{} {}",
-            Icon::Calendar,
-            now.format("%Y-%m-%d %T")
-        )))
+        let values = Values::new()
+            .with("icon", Icon::Calendar.to_string())
+            .with("date", now.format("%Y-%m-%d").to_string())
+            .with("time", now.format("%T").to_string());
+
+        Ok(BlockState::from_markup(self.format.render(&values)))
     }
 
     fn handle_click(&self, _event: ClickEvent) {}
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
 }
 
 impl DateTime {
-    pub fn new() -> DateTime {
-        DateTime {}
+    pub fn new(config: DateTimeConfig) -> DateTime {
+        DateTime {
+            format: config.format,
+        }
     }
 }
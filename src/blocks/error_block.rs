@@ -0,0 +1,31 @@
+use crate::block::{Block, BlockError, BlockState, ClickEvent};
+use crate::error::Error;
+use std::time::Duration;
+
+/// Stands in for a block that could not be created, so that the problem is
+/// visible on the bar instead of only in the log.
+pub struct ErrorBlock {
+    message: String,
+}
+
+impl Block for ErrorBlock {
+    fn current_state(&mut self) -> Result<BlockState, BlockError> {
+        Ok(BlockState::new(self.message.clone())
+            .with_short_text("error".into())
+            .with_color("#ff0000".into()))
+    }
+
+    fn handle_click(&self, _event: ClickEvent) {}
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl ErrorBlock {
+    pub fn new(error: &Error) -> Self {
+        ErrorBlock {
+            message: error.to_string(),
+        }
+    }
+}
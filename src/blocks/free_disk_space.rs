@@ -1,27 +1,73 @@
-use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon};
+use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon, Thresholds};
+use crate::format::{Format, Values};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub struct FreeDiskSpace {}
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct FreeDiskSpaceConfig {
+    /// Any path on the filesystem to report.
+    path: PathBuf,
+    /// Knows `icon`, `free` and `total` in gigabytes, and `percent` free.
+    format: Format,
+    /// Gigabytes free below which the block turns into a warning.
+    warning: Option<f64>,
+    critical: Option<f64>,
+}
+
+impl Default for FreeDiskSpaceConfig {
+    fn default() -> Self {
+        FreeDiskSpaceConfig {
+            path: "/".into(),
+            format: "{icon} {free:.2} GB".parse().unwrap(),
+            warning: None,
+            critical: None,
+        }
+    }
+}
+
+pub struct FreeDiskSpace {
+    path: PathBuf,
+    format: Format,
+    thresholds: Thresholds,
+}
 
 impl Block for FreeDiskSpace {
     fn current_state(&mut self) -> Result<BlockState, BlockError> {
-        let stats = nix::sys::statvfs::statvfs("/")
-            .map_err(|_| BlockError::new("Failed to stat".to_string()))?;
+        let stats = nix::sys::statvfs::statvfs(self.path.as_path())
+            .map_err(|_| BlockError::new(format!("Failed to stat {}", self.path.display())))?;
+
+        let gigabyte = 1024.0f64.powi(3);
+        let bytes_free = stats.blocks_available() * stats.fragment_size();
+        let bytes_total = stats.blocks() * stats.fragment_size();
+        let gigabytes_free = bytes_free as f64 / gigabyte;
+        let mut values = Values::new()
+            .with("icon", Icon::HDD.to_string())
+            .with("free", gigabytes_free)
+            .with("total", bytes_total as f64 / gigabyte);
 
-        let bytes_free = stats.blocks_available() * stats.block_size();
-        let gigabytes_free = bytes_free as f32 / (1024.0f32.powi(3));
+        if bytes_total > 0 {
+            values = values.with("percent", bytes_free as f64 * 100.0 / bytes_total as f64);
+        }
 
-        Ok(BlockState::new(format!(
-            "{} {:.2} GB",
-            Icon::HDD,
-            gigabytes_free
-        )))
+        Ok(BlockState::from_markup(self.format.render(&values))
+            .with_severity(self.thresholds.below(gigabytes_free)))
     }
 
     fn handle_click(&self, _event: ClickEvent) {}
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(30))
+    }
 }
 
 impl FreeDiskSpace {
-    pub fn new() -> Self {
-        FreeDiskSpace {}
+    pub fn new(config: FreeDiskSpaceConfig) -> Self {
+        FreeDiskSpace {
+            path: config.path,
+            format: config.format,
+            thresholds: Thresholds::new(config.warning, config.critical),
+        }
     }
 }
@@ -1,6 +1,8 @@
-use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon};
+use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon, Notifier};
+use crate::format::{Format, Values};
 use mpris::{DBusError, FindingError, Player, PlayerFinder};
-use std::sync::mpsc::{Receiver, Sender};
+use serde::Deserialize;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::thread;
 use std::thread::JoinHandle;
 use std::time::Duration;
@@ -8,17 +10,40 @@ use std::time::Duration;
 enum MediaPlayerRequest {
     Quit,
     TogglePause,
+    Next,
+    Previous,
+    Suspend,
+    Resume,
 }
 
 enum MediaPlayerStateChange {
-    NowPlaying { artist: String, title: String },
+    NowPlaying {
+        artist: Option<String>,
+        title: String,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MediaPlayerConfig {
+    /// Knows `icon`, `artist` and `title`.
+    format: Format,
+}
+
+impl Default for MediaPlayerConfig {
+    fn default() -> Self {
+        MediaPlayerConfig {
+            format: "{icon} [{artist} - ]{title}".parse().unwrap(),
+        }
+    }
 }
 
 pub struct MediaPlayer {
     thread: Option<JoinHandle<()>>,
     command_sender: Sender<MediaPlayerRequest>,
     state_receiver: Receiver<MediaPlayerStateChange>,
-    current_state: String,
+    now_playing: Option<Values>,
+    format: Format,
 }
 
 impl Block for MediaPlayer {
@@ -28,22 +53,49 @@ impl Block for MediaPlayer {
         if let Some(new_state) = state {
             match new_state {
                 MediaPlayerStateChange::NowPlaying { artist, title } => {
-                    self.current_state = format!("{} {} - {}", Icon::Music, artist, title);
+                    self.now_playing = Some(
+                        Values::new()
+                            .with("icon", Icon::Music.to_string())
+                            .with_optional("artist", artist)
+                            .with("title", title),
+                    );
                 }
             }
         };
 
-        if self.current_state != "" {
-            Ok(BlockState::new(self.current_state.clone()))
-        } else {
-            Err(BlockError::new("Unknown state".into()))
+        match &self.now_playing {
+            Some(values) => Ok(BlockState::from_markup(self.format.render(values))),
+            None => Err(BlockError::new("Unknown state".into())),
         }
     }
 
     fn handle_click(&self, _event: ClickEvent) {
-        self.command_sender
-            .send(MediaPlayerRequest::TogglePause)
-            .unwrap();
+        self.run_action("play_pause");
+    }
+
+    fn actions(&self) -> &'static [&'static str] {
+        &["play_pause", "next", "previous"]
+    }
+
+    fn run_action(&self, action: &str) {
+        match action {
+            "play_pause" => self.send_request(MediaPlayerRequest::TogglePause),
+            "next" => self.send_request(MediaPlayerRequest::Next),
+            "previous" => self.send_request(MediaPlayerRequest::Previous),
+            _ => {}
+        }
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    fn pause(&mut self) {
+        self.send_request(MediaPlayerRequest::Suspend);
+    }
+
+    fn resume(&mut self) {
+        self.send_request(MediaPlayerRequest::Resume);
     }
 }
 
@@ -83,17 +135,14 @@ impl From<DBusError> for PlayerError {
     }
 }
 
-fn get_artist_and_title(player:&Player) -> Result<Option<(String, String)>, PlayerError> {
+fn get_artist_and_title(player:&Player) -> Result<Option<(Option<String>, String)>, PlayerError> {
     let metadata = player.get_metadata()?;
-    let artists;
+    let artists = metadata
+        .artists()
+        .filter(|artists| !artists.is_empty())
+        .map(|artists| artists.join(", "));
     let title;
 
-    if let Some(artists_vec) = metadata.artists() {
-        artists = artists_vec.join(", ");
-    } else {
-        return Ok(None);
-    }
-
     if let Some(title_opt) = metadata.title() {
         title = title_opt.into();
     } else {
@@ -104,7 +153,7 @@ fn get_artist_and_title(player:&Player) -> Result<Option<(String, String)>, Play
 }
 
 impl MediaPlayer {
-    pub fn new() -> Self {
+    pub fn new(config: MediaPlayerConfig, notifier: Notifier) -> Self {
         let (command_sender, command_receiver): (
             Sender<MediaPlayerRequest>,
             Receiver<MediaPlayerRequest>,
@@ -115,50 +164,89 @@ impl MediaPlayer {
             Receiver<MediaPlayerStateChange>,
         ) = std::sync::mpsc::channel();
 
+        let mut now_playing = None;
+        let mut suspended = false;
+
         MediaPlayer {
             thread: Some(thread::spawn(move || 'mainloop: loop {
-                let message = command_receiver.recv_timeout(Duration::from_millis(500));
+                let message = if suspended {
+                    command_receiver
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected)
+                } else {
+                    command_receiver.recv_timeout(Duration::from_millis(500))
+                };
+
+                match message {
+                    Ok(MediaPlayerRequest::Quit) | Err(RecvTimeoutError::Disconnected) => {
+                        break 'mainloop
+                    }
+                    Ok(MediaPlayerRequest::Suspend) => {
+                        suspended = true;
+                        continue;
+                    }
+                    Ok(MediaPlayerRequest::Resume) => suspended = false,
+                    Ok(MediaPlayerRequest::TogglePause)
+                    | Ok(MediaPlayerRequest::Next)
+                    | Ok(MediaPlayerRequest::Previous)
+                    | Err(RecvTimeoutError::Timeout) => {}
+                }
 
-                let should_continue:Result<bool, PlayerError> = find_player()
+                let result: Result<(), PlayerError> = find_player()
                     .map(|player| {
                         let artist_and_title = get_artist_and_title(&player)?;
 
-                        if let Some((artist, title)) = artist_and_title {
-                            state_sender
-                                .send(MediaPlayerStateChange::NowPlaying {
-                                    artist,
-                                    title,
-                                })
-                                .unwrap();
+                        if artist_and_title.is_some() && artist_and_title != now_playing {
+                            now_playing = artist_and_title.clone();
+
+                            if let Some((artist, title)) = artist_and_title {
+                                state_sender
+                                    .send(MediaPlayerStateChange::NowPlaying {
+                                        artist,
+                                        title,
+                                    })
+                                    .unwrap();
+                                notifier.notify();
+                            }
                         }
 
                         match message {
-                            Result::Ok(MediaPlayerRequest::Quit) => Ok(false),
-                            Result::Ok(MediaPlayerRequest::TogglePause) => {
-                                player.play_pause().unwrap();
-                                Ok(true)
-                            }
-                            Result::Err(_) => Ok(true),
+                            Ok(MediaPlayerRequest::TogglePause) => player.play_pause()?,
+                            Ok(MediaPlayerRequest::Next) => player.next()?,
+                            Ok(MediaPlayerRequest::Previous) => player.previous()?,
+                            _ => {}
                         }
+
+                        Ok(())
                     })
-                    .unwrap_or(Ok(true));
+                    .unwrap_or(Ok(()));
 
-                if let Ok(false) = should_continue {
-                    break 'mainloop;
+                if let Err(e) = result {
+                    warn!("{:?}", e);
                 }
-
-                // fixme log error
             })),
             command_sender,
             state_receiver,
-            current_state: "".into(),
+            now_playing: None,
+            format: config.format,
+        }
+    }
+
+    fn send_request(&self, request: MediaPlayerRequest) {
+        if self.command_sender.send(request).is_err() {
+            error!("Media player thread is gone");
         }
     }
 }
 
 impl Drop for MediaPlayer {
     fn drop(&mut self) {
-        self.command_sender.send(MediaPlayerRequest::Quit).unwrap();
-        self.thread.take().unwrap().join().unwrap();
+        if self.command_sender.send(MediaPlayerRequest::Quit).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                if thread.join().is_err() {
+                    error!("Media player thread panicked");
+                }
+            }
+        }
     }
 }
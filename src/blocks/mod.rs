@@ -1,4 +1,6 @@
+pub mod command;
 pub mod date_time;
+pub mod error_block;
 pub mod free_disk_space;
 pub mod media_player;
 pub mod network_interface;
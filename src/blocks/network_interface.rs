@@ -1,8 +1,24 @@
 use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon};
+use crate::format::{Format, Values};
 use nix::sys::socket::{SockAddr, AddressFamily};
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+pub struct NetworkInterfaceConfig {
+    interface: String,
+    /// Knows `icon`, `interface` and `ip`.
+    #[serde(default = "default_format")]
+    format: Format,
+}
+
+fn default_format() -> Format {
+    "{icon} {ip}".parse().unwrap()
+}
 
 pub struct NetworkInterface {
     interface: String,
+    format: Format,
 }
 
 impl From<nix::Error> for BlockError {
@@ -22,7 +38,12 @@ impl Block for NetworkInterface {
         match iface {
             Some(i) => match i.address {
                 Some(SockAddr::Inet(address)) => {
-                    Ok(BlockState::new(format!("{} {}", Icon::Globe, address.ip())))
+                    let values = Values::new()
+                        .with("icon", Icon::Globe.to_string())
+                        .with("interface", self.interface.as_str())
+                        .with("ip", address.ip().to_string());
+
+                    Ok(BlockState::from_markup(self.format.render(&values)))
                 }
                 Some(address) => Err(BlockError::new(format!("Wrong address type: {:?}", address))),
                 None => Err(BlockError::new("No address".to_string())),
@@ -34,10 +55,17 @@ impl Block for NetworkInterface {
     }
 
     fn handle_click(&self, _event: ClickEvent) {}
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(10))
+    }
 }
 
 impl NetworkInterface {
-    pub fn new(interface: String) -> Self {
-        NetworkInterface { interface }
+    pub fn new(config: NetworkInterfaceConfig) -> Self {
+        NetworkInterface {
+            interface: config.interface,
+            format: config.format,
+        }
     }
 }
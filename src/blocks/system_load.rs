@@ -1,31 +1,60 @@
-use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon};
+use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon, Thresholds};
+use crate::format::{Format, Values};
+use serde::Deserialize;
 use std::os::raw::c_double;
 
-pub struct SystemLoad {}
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SystemLoadConfig {
+    /// Knows `icon`, `load1`, `load5` and `load15`.
+    format: Format,
+    /// One minute load above which the block turns into a warning.
+    warning: Option<f64>,
+    critical: Option<f64>,
+}
+
+impl Default for SystemLoadConfig {
+    fn default() -> Self {
+        SystemLoadConfig {
+            format: "{icon} {load1:.2}".parse().unwrap(),
+            warning: None,
+            critical: None,
+        }
+    }
+}
+
+pub struct SystemLoad {
+    format: Format,
+    thresholds: Thresholds,
+}
 
 impl Block for SystemLoad {
     fn current_state(&mut self) -> Result<BlockState, BlockError> {
-        let mut load_averages: [c_double; 1] = [0f64];
-        let received = unsafe { libc::getloadavg(load_averages.as_mut_ptr(), 1) };
+        let mut load_averages: [c_double; 3] = [0f64; 3];
+        let received = unsafe { libc::getloadavg(load_averages.as_mut_ptr(), 3) };
 
-        if received != 1 {
+        if received != 3 {
             return Err(BlockError::new("Cannot get load average!".to_string()));
         }
 
-        let load = load_averages[0];
+        let values = Values::new()
+            .with("icon", Icon::LightningBolt.to_string())
+            .with("load1", load_averages[0])
+            .with("load5", load_averages[1])
+            .with("load15", load_averages[2]);
 
-        Ok(BlockState::new(format!(
-            "{} {:.2}",
-            Icon::LightningBolt,
-            load
-        )))
+        Ok(BlockState::from_markup(self.format.render(&values))
+            .with_severity(self.thresholds.above(load_averages[0])))
     }
 
     fn handle_click(&self, _event: ClickEvent) {}
 }
 
 impl SystemLoad {
-    pub fn new() -> SystemLoad {
-        SystemLoad {}
+    pub fn new(config: SystemLoadConfig) -> SystemLoad {
+        SystemLoad {
+            format: config.format,
+            thresholds: Thresholds::new(config.warning, config.critical),
+        }
     }
 }
@@ -1,79 +1,268 @@
-use crate::block::{Block, BlockError, BlockState, ClickEvent, Icon, MouseButton};
-use libpulse_binding::callbacks::ListResult::Item;
+use crate::block::{
+    Block, BlockError, BlockState, ClickEvent, Icon, MouseButton, Notifier, Severity,
+};
+use crate::format::{Format, Values};
+use crate::markup::Markup;
+use libpulse_binding::callbacks::ListResult;
+use libpulse_binding::context::introspect::Introspector;
+use libpulse_binding::context::subscribe::{subscription_masks, Facility};
 use libpulse_binding::context::Context;
 use libpulse_binding::mainloop::threaded::Mainloop;
-use libpulse_binding::volume::ChannelVolumes;
+use libpulse_binding::proplist::properties;
+use libpulse_binding::volume::{
+    ChannelVolumes, Volume as PulseVolume, VolumeDB, VolumeLinear, VOLUME_MAX, VOLUME_MUTED,
+    VOLUME_NORM,
+};
+use serde::de;
+use serde::{Deserialize, Deserializer};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::string::ToString;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the state of a new connection is checked.
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Called on the mainloop thread for every event of a facility the factory
+/// subscribed to, with an introspector of the context.
+type Listener = Box<dyn FnMut(&Introspector, Facility) + Send>;
+
+/// Shared by the mainloop thread, which dispatches events to them, and ours,
+/// which adds and removes them.
+type Listeners = Arc<Mutex<HashMap<usize, Listener>>>;
 
 pub struct VolumeFactory {
-    context: Rc<Context>,
-    #[allow(unused)]
-    main_loop: Rc<Mainloop>,
+    context: Rc<RefCell<Context>>,
+    listeners: Listeners,
+    next_listener: Cell<usize>,
+    main_loop: Rc<SharedMainloop>,
+}
+
+/// The threaded mainloop, along with how many `MainloopLock`s are held on it.
+struct SharedMainloop {
+    main_loop: RefCell<Mainloop>,
+    locks: Cell<usize>,
+}
+
+/// Holds the lock of the threaded mainloop, which libpulse requires for
+/// every use of the context outside of its callbacks. Locks can be nested,
+/// only the outermost one locks and unlocks the mainloop.
+struct MainloopLock<'a>(&'a SharedMainloop);
+
+impl<'a> MainloopLock<'a> {
+    fn new(main_loop: &'a SharedMainloop) -> Self {
+        if main_loop.locks.get() == 0 {
+            main_loop.main_loop.borrow_mut().lock();
+        }
+
+        main_loop.locks.set(main_loop.locks.get() + 1);
+
+        MainloopLock(main_loop)
+    }
+}
+
+impl Drop for MainloopLock<'_> {
+    fn drop(&mut self) {
+        let locks = self.0.locks.get() - 1;
+        self.0.locks.set(locks);
+
+        if locks == 0 {
+            self.0.main_loop.borrow_mut().unlock();
+        }
+    }
+}
+
+/// Keeps a listener registered for as long as the block owning it lives.
+struct Subscription {
+    id: usize,
+    listeners: Listeners,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Ok(mut listeners) = self.listeners.lock() {
+            listeners.remove(&self.id);
+        }
+    }
+}
+
+/// The default sink follows whatever is chosen in pulseaudio, e.g. by
+/// `on_right_click = { action = "next_sink" }`.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct VolumeConfig {
+    sink: String,
+    /// Knows `icon`, which depends on the device, `volume` as pulseaudio
+    /// prints it, `percent` and `db` of the loudest channel, `device`, `port`
+    /// if the sink has ports, and `muted` while the sink is muted.
+    format: Format,
+    /// What `device` shows instead of the sink description, by port or sink
+    /// name, e.g. `"analog-output-headphones" = "Phones"`.
+    names: HashMap<String, String>,
+    /// How much scrolling changes the volume, in percent, or in decibels
+    /// with the `db` curve.
+    #[serde(deserialize_with = "deserialize_step")]
+    step: f64,
+    curve: Curve,
+    /// Scrolling up stops here, in percent.
+    max_volume: u32,
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        VolumeConfig {
+            sink: "@DEFAULT_SINK@".into(),
+            format: "{icon} {volume}".parse().unwrap(),
+            names: HashMap::new(),
+            step: DEFAULT_STEP,
+            curve: Curve::default(),
+            max_volume: DEFAULT_MAX_VOLUME,
+        }
+    }
 }
 
 pub struct Volume {
     sink_name: String,
-    context: Rc<Context>,
+    format: Format,
+    names: HashMap<String, String>,
+    stepping: Stepping,
+    /// Updated on the mainloop thread whenever a sink or the server changes.
+    state: Arc<Mutex<SinkState>>,
     #[allow(unused)]
-    main_loop: Rc<Mainloop>,
+    subscription: Subscription,
+    context: Rc<RefCell<Context>>,
+    main_loop: Rc<SharedMainloop>,
+}
+
+#[derive(Default)]
+struct SinkState {
+    sink: Option<SinkInfo>,
+    /// All sinks, in the order pulseaudio lists them.
+    sinks: Vec<String>,
+    default_sink: Option<String>,
 }
 
+#[derive(PartialEq)]
 struct SinkInfo {
-    current_volume: String,
-    channels: u8,
-    volume: u32,
+    /// The actual name, also when configured as `@DEFAULT_SINK@`.
+    name: String,
+    description: String,
+    port: Option<Port>,
+    device: Device,
+    volume: ChannelVolumes,
     muted: bool,
 }
 
-lazy_static! {
-    static ref SINK_VOLUME: Mutex<HashMap<String, SinkInfo>> = Mutex::new(HashMap::new());
+#[derive(PartialEq)]
+struct Port {
+    name: String,
+    description: String,
+}
+
+/// What a sink plays through, judging by its active port and form factor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Device {
+    Speakers,
+    Headphones,
+    Hdmi,
+}
+
+impl Device {
+    fn detect(port: Option<&str>, form_factor: Option<&str>) -> Device {
+        let port = port.unwrap_or("").to_lowercase();
+
+        if port.contains("hdmi") || port.contains("iec958") || form_factor == Some("tv") {
+            Device::Hdmi
+        } else if port.contains("headphone")
+            || port.contains("headset")
+            || matches!(form_factor, Some("headphone" | "headset" | "hands-free"))
+        {
+            Device::Headphones
+        } else {
+            Device::Speakers
+        }
+    }
+
+    fn icon(self) -> Icon {
+        match self {
+            Device::Speakers => Icon::VolumeUp,
+            Device::Headphones => Icon::Headphones,
+            Device::Hdmi => Icon::Tv,
+        }
+    }
 }
 
 impl Block for Volume {
     fn current_state(&mut self) -> Result<BlockState, BlockError> {
-        let sink_name = self.sink_name.clone();
-        self.context
-            .introspect()
-            .get_sink_info_by_name(&self.sink_name, move |info| {
-                if let Item(sink_info) = info {
-                    let mut volume_map = SINK_VOLUME.lock().unwrap();
-                    let linear = (*sink_info).volume.avg();
-
-                    volume_map.insert(
-                        sink_name.clone(),
-                        SinkInfo {
-                            volume: linear.0,
-                            current_volume: (*sink_info).volume.avg().print(),
-                            channels: (*sink_info).volume.channels,
-                            muted: (*sink_info).mute,
-                        },
-                    );
-                }
-            });
+        let state = self.state.lock().unwrap();
+
+        match &state.sink {
+            Some(i) => {
+                let device = i
+                    .port
+                    .as_ref()
+                    .and_then(|port| self.names.get(&port.name))
+                    .or_else(|| self.names.get(&i.name))
+                    .unwrap_or(&i.description);
+                let values = volume_values(&i.volume)
+                    .with("icon", i.device.icon().to_string())
+                    .with("device", device.as_str())
+                    .with_optional(
+                        "port",
+                        i.port.as_ref().map(|port| port.description.as_str()),
+                    )
+                    .with_optional("muted", if i.muted { Some("muted") } else { None });
 
-        let guard = SINK_VOLUME.lock().unwrap();
-        let sink_volume = guard.get(&self.sink_name);
+                let severity = if i.muted {
+                    Severity::Warning
+                } else {
+                    Severity::Idle
+                };
 
-        match sink_volume {
-            Some(i) => Ok(BlockState::new(format!(
-                "{} {}",
-                Icon::VolumeUp,
-                i.current_volume
-            ))),
+                Ok(BlockState::from_markup(self.format.render(&values)).with_severity(severity))
+            }
             None => Err(BlockError::new("Unknown volume".to_string())),
         }
     }
 
     fn handle_click(&self, event: ClickEvent) {
-        let guard = SINK_VOLUME.lock().unwrap();
-        let sink_volume = guard.get(&self.sink_name).unwrap();
+        match event.button() {
+            MouseButton::Left => self.run_action("toggle_mute"),
+            MouseButton::ScrollUp => self.run_action("volume_up"),
+            MouseButton::ScrollDown => self.run_action("volume_down"),
+            _ => {}
+        }
+    }
+
+    fn actions(&self) -> &'static [&'static str] {
+        &["toggle_mute", "volume_up", "volume_down", "next_sink"]
+    }
+
+    fn run_action(&self, action: &str) {
+        let _lock = MainloopLock::new(&self.main_loop);
+        let state = self.state.lock().unwrap();
 
-        if let MouseButton::Left = event.button() {
-            self.context.introspect().set_sink_mute_by_name(
-                &self.sink_name,
+        if action == "next_sink" {
+            self.switch_to_next_sink(&state);
+            return;
+        }
+
+        let sink_volume = match &state.sink {
+            Some(sink_volume) => sink_volume,
+            None => {
+                warn!(
+                    "Ignoring {}, volume of {} is not known yet",
+                    action, self.sink_name
+                );
+                return;
+            }
+        };
+
+        if action == "toggle_mute" {
+            self.context.borrow().introspect().set_sink_mute_by_name(
+                &sink_volume.name,
                 !sink_volume.muted,
                 Some(Box::new(|st| info!("{:?}", st))),
             );
@@ -81,60 +270,1005 @@ impl Block for Volume {
             return;
         }
 
-        let step = (libpulse_binding::volume::VOLUME_NORM.0
-            - libpulse_binding::volume::VOLUME_MUTED.0)
-            / 20;
+        let channel_volumes = self.stepping.apply(&sink_volume.volume, action);
 
-        let final_volume = match event.button() {
-            MouseButton::ScrollUp => sink_volume.volume.saturating_add(step),
-            MouseButton::ScrollDown => sink_volume.volume.saturating_sub(step),
-            _ => sink_volume.volume,
-        };
+        self.context.borrow().introspect().set_sink_volume_by_name(
+            &sink_volume.name,
+            &channel_volumes,
+            None,
+        );
+    }
 
-        let channel_volumes = ChannelVolumes {
-            channels: sink_volume.channels,
-            values: [libpulse_binding::volume::Volume(final_volume); 32],
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Volume {
+    /// Makes the sink after the current default one the default. Needs the
+    /// mainloop locked.
+    fn switch_to_next_sink(&self, state: &SinkState) {
+        let current = state
+            .default_sink
+            .as_ref()
+            .and_then(|default| state.sinks.iter().position(|sink| sink == default));
+        let next = match current {
+            Some(i) => &state.sinks[(i + 1) % state.sinks.len()],
+            None => match state.sinks.first() {
+                Some(sink) => sink,
+                None => {
+                    warn!("Ignoring next_sink, there are no sinks");
+                    return;
+                }
+            },
         };
 
+        let name = next.clone();
         self.context
-            .introspect()
-            .set_sink_volume_by_name(&self.sink_name, &channel_volumes, None);
+            .borrow_mut()
+            .set_default_sink(next, move |success| {
+                if !success {
+                    error!("Cannot make {} the default sink", name);
+                }
+            });
+    }
+}
+
+/// The values every volume format knows.
+fn volume_values(volume: &ChannelVolumes) -> Values {
+    let loudest = volume.max();
+    let percent = u64::from(loudest.0) * 100 / u64::from(VOLUME_NORM.0);
+
+    Values::new()
+        .with("volume", loudest.print().trim())
+        .with("percent", percent as i64)
+        .with("db", VolumeDB::from(loudest).0.max(DB_FLOOR))
+}
+
+const DEFAULT_STEP: f64 = 5.0;
+const DEFAULT_MAX_VOLUME: u32 = 100;
+
+/// Quieter than this, a volume counts as muted when stepping in decibels,
+/// which would otherwise never get anywhere from silence. Silence shows as
+/// this in `db` too, rather than as negative infinity.
+const DB_FLOOR: f64 = -60.0;
+
+/// A step that isn't a positive number would make scrolling do nothing, or
+/// the opposite of what it should.
+fn deserialize_step<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let step = f64::deserialize(deserializer)?;
+
+    if step.is_finite() && step > 0.0 {
+        Ok(step)
+    } else {
+        Err(de::Error::custom(format!(
+            "step must be a positive number, not {}",
+            step
+        )))
+    }
+}
+
+/// What the step of scrolling is measured in.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    /// Percent of the volume as pulseaudio and pavucontrol show it, which is
+    /// the cube root of the amplitude.
+    #[default]
+    Cubic,
+    /// Percent of the amplitude.
+    Linear,
+    /// Decibels.
+    Db,
+}
+
+/// How scrolling changes a volume.
+#[derive(Debug, Copy, Clone)]
+struct Stepping {
+    step: f64,
+    curve: Curve,
+    max_volume: PulseVolume,
+}
+
+impl Stepping {
+    fn new(step: f64, curve: Curve, max_volume: u32) -> Self {
+        let max_volume = u64::from(VOLUME_NORM.0) * u64::from(max_volume) / 100;
+
+        Stepping {
+            step,
+            curve,
+            max_volume: PulseVolume(max_volume.min(u64::from(VOLUME_MAX.0)) as u32),
+        }
+    }
+
+    /// The volume one step up or down, for `volume_up` and `volume_down`.
+    /// Only the loudest channel steps, the others keep their balance to it.
+    fn apply(&self, volume: &ChannelVolumes, action: &str) -> ChannelVolumes {
+        let loudest = volume.max();
+
+        let target = match action {
+            "volume_up" => self.step(loudest, 1.0).min(self.max_volume).max(loudest),
+            "volume_down" => self.step(loudest, -1.0).min(loudest),
+            _ => loudest,
+        };
+
+        scale(volume, target)
+    }
+
+    fn step(&self, volume: PulseVolume, direction: f64) -> PulseVolume {
+        let step = self.step * direction;
+
+        match self.curve {
+            Curve::Cubic => {
+                let norm = f64::from(VOLUME_NORM.0);
+                let stepped = f64::from(volume.0) + step / 100.0 * norm;
+
+                PulseVolume(stepped.max(0.0).round() as u32)
+            }
+            Curve::Linear => {
+                let stepped = VolumeLinear::from(volume).0 + step / 100.0;
+
+                VolumeLinear(stepped.max(0.0)).into()
+            }
+            Curve::Db => {
+                let stepped = VolumeDB::from(volume).0.max(DB_FLOOR) + step;
+
+                if stepped <= DB_FLOOR {
+                    VOLUME_MUTED
+                } else {
+                    VolumeDB(stepped).into()
+                }
+            }
+        }
+    }
+}
+
+/// Sets the loudest channel to `target`, keeping the proportions between the
+/// channels like `pa_cvolume_scale`. The binding's `scale` passes pulseaudio
+/// a wrong pointer.
+fn scale(volume: &ChannelVolumes, target: PulseVolume) -> ChannelVolumes {
+    let loudest = u64::from(volume.max().0);
+    let mut scaled = *volume;
+
+    for value in scaled.values[..usize::from(volume.channels)].iter_mut() {
+        // When all channels are silent there is no balance to keep.
+        value.0 = (u64::from(value.0) * u64::from(target.0))
+            .checked_div(loudest)
+            .map_or(target.0, |scaled| scaled as u32);
+    }
+
+    scaled
+}
+
+/// Reads the sink into the state, and redraws the block if anything changed.
+fn query_sink(
+    introspector: &Introspector,
+    name: &str,
+    state: Arc<Mutex<SinkState>>,
+    notifier: Notifier,
+) {
+    introspector.get_sink_info_by_name(name, move |result| {
+        let new_info = match result {
+            ListResult::Item(sink_info) => {
+                let port = sink_info.active_port.as_ref().map(|port| Port {
+                    name: port.name.as_deref().unwrap_or("").to_owned(),
+                    description: port.description.as_deref().unwrap_or("").to_owned(),
+                });
+                let form_factor = sink_info.proplist.gets(properties::DEVICE_FORM_FACTOR);
+                let name = sink_info.name.as_deref().unwrap_or("").to_owned();
+
+                Some(SinkInfo {
+                    device: Device::detect(
+                        port.as_ref().map(|port| port.name.as_str()),
+                        form_factor.as_deref(),
+                    ),
+                    description: sink_info
+                        .description
+                        .as_deref()
+                        .map_or_else(|| name.clone(), str::to_owned),
+                    name,
+                    port,
+                    volume: sink_info.volume,
+                    muted: sink_info.mute,
+                })
+            }
+            // The sink is gone.
+            ListResult::Error => None,
+            ListResult::End => return,
+        };
+
+        let mut state = state.lock().unwrap();
+
+        if state.sink != new_info {
+            state.sink = new_info;
+            notifier.notify();
+        }
+    });
+}
+
+/// Reads the names of all sinks, to know which one is next.
+fn query_sinks(introspector: &Introspector, state: Arc<Mutex<SinkState>>) {
+    let mut sinks = vec![];
+
+    introspector.get_sink_info_list(move |result| match result {
+        ListResult::Item(sink_info) => {
+            sinks.extend(sink_info.name.as_deref().map(str::to_owned));
+        }
+        ListResult::End => state.lock().unwrap().sinks = std::mem::take(&mut sinks),
+        ListResult::Error => warn!("Cannot list the pulse sinks"),
+    });
+}
+
+fn query_default_sink(introspector: &Introspector, state: Arc<Mutex<SinkState>>) {
+    introspector.get_server_info(move |server_info| {
+        state.lock().unwrap().default_sink =
+            server_info.default_sink_name.as_deref().map(str::to_owned);
+    });
+}
+
+/// Shows a source, usually the microphone, and goes urgent while something
+/// records from it unmuted.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MicrophoneConfig {
+    source: String,
+    /// Knows `icon`, `volume` as pulseaudio prints it, `percent` and `db` of
+    /// the loudest channel, `device`, and `muted` while the source is muted.
+    format: Format,
+    /// Scrolling works like for the volume block.
+    #[serde(deserialize_with = "deserialize_step")]
+    step: f64,
+    curve: Curve,
+    max_volume: u32,
+}
+
+impl Default for MicrophoneConfig {
+    fn default() -> Self {
+        MicrophoneConfig {
+            source: "@DEFAULT_SOURCE@".into(),
+            format: "{icon} {volume}".parse().unwrap(),
+            step: DEFAULT_STEP,
+            curve: Curve::default(),
+            max_volume: DEFAULT_MAX_VOLUME,
+        }
+    }
+}
+
+pub struct Microphone {
+    source_name: String,
+    format: Format,
+    stepping: Stepping,
+    /// Updated on the mainloop thread whenever the source changes.
+    source: Arc<Mutex<Option<SourceInfo>>>,
+    #[allow(unused)]
+    subscription: Subscription,
+    context: Rc<RefCell<Context>>,
+    main_loop: Rc<SharedMainloop>,
+}
+
+#[derive(PartialEq)]
+struct SourceInfo {
+    /// The actual name, also when configured as `@DEFAULT_SOURCE@`.
+    name: String,
+    description: String,
+    volume: ChannelVolumes,
+    muted: bool,
+    /// Whether anything records from the source.
+    running: bool,
+}
+
+impl Block for Microphone {
+    fn current_state(&mut self) -> Result<BlockState, BlockError> {
+        let source = self.source.lock().unwrap();
+
+        match &*source {
+            Some(i) => {
+                let icon = if i.muted {
+                    Icon::MicrophoneOff
+                } else {
+                    Icon::Microphone
+                };
+                let values = volume_values(&i.volume)
+                    .with("icon", icon.to_string())
+                    .with("device", i.description.as_str())
+                    .with_optional("muted", if i.muted { Some("muted") } else { None });
+                let state = BlockState::from_markup(self.format.render(&values));
+
+                Ok(if i.running && !i.muted {
+                    state.with_severity(Severity::Critical).with_urgent(true)
+                } else {
+                    state
+                })
+            }
+            None => Err(BlockError::new("Unknown microphone volume".to_string())),
+        }
+    }
+
+    fn handle_click(&self, event: ClickEvent) {
+        match event.button() {
+            MouseButton::Left => self.run_action("toggle_mute"),
+            MouseButton::ScrollUp => self.run_action("volume_up"),
+            MouseButton::ScrollDown => self.run_action("volume_down"),
+            _ => {}
+        }
+    }
+
+    fn actions(&self) -> &'static [&'static str] {
+        &["toggle_mute", "volume_up", "volume_down"]
+    }
+
+    fn run_action(&self, action: &str) {
+        let _lock = MainloopLock::new(&self.main_loop);
+        let source = self.source.lock().unwrap();
+        let source = match &*source {
+            Some(source) => source,
+            None => {
+                warn!(
+                    "Ignoring {}, volume of {} is not known yet",
+                    action, self.source_name
+                );
+                return;
+            }
+        };
+
+        let mut introspector = self.context.borrow().introspect();
+
+        if action == "toggle_mute" {
+            introspector.set_source_mute_by_name(&source.name, !source.muted, None);
+        } else {
+            let channel_volumes = self.stepping.apply(&source.volume, action);
+            introspector.set_source_volume_by_name(&source.name, &channel_volumes, None);
+        }
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Reads the source, and redraws the block if anything changed.
+fn query_source(
+    introspector: &Introspector,
+    name: &str,
+    source: Arc<Mutex<Option<SourceInfo>>>,
+    notifier: Notifier,
+) {
+    introspector.get_source_info_by_name(name, move |result| {
+        let new_info = match result {
+            ListResult::Item(source_info) => {
+                let name = source_info.name.as_deref().unwrap_or("").to_owned();
+
+                Some(SourceInfo {
+                    description: source_info
+                        .description
+                        .as_deref()
+                        .map_or_else(|| name.clone(), str::to_owned),
+                    name,
+                    volume: source_info.volume,
+                    muted: source_info.mute,
+                    running: source_info.state.is_running(),
+                })
+            }
+            // The source is gone.
+            ListResult::Error => None,
+            ListResult::End => return,
+        };
+
+        let mut source = source.lock().unwrap();
+
+        if *source != new_info {
+            *source = new_info;
+            notifier.notify();
+        }
+    });
+}
+
+/// Lists the applications playing audio. Clicks select one of them, whose
+/// volume scrolling then changes, and which a right click moves to the next
+/// sink.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ApplicationsConfig {
+    /// Knows `icon`, `streams` while there are any, and `count`.
+    format: Format,
+    /// How each stream is shown in `streams`. Knows `name`, `volume` as
+    /// pulseaudio prints it, `percent` and `db` of the loudest channel,
+    /// `sink`, `muted` while the stream is muted, and `paused` while it is
+    /// paused.
+    stream_format: Format,
+    /// How the selected stream is shown, with the same values.
+    selected_format: Format,
+    /// Scrolling works like for the volume block.
+    #[serde(deserialize_with = "deserialize_step")]
+    step: f64,
+    curve: Curve,
+    max_volume: u32,
+}
+
+impl Default for ApplicationsConfig {
+    fn default() -> Self {
+        ApplicationsConfig {
+            format: "{icon}[ {streams}]".parse().unwrap(),
+            stream_format: "{name}[ ({paused})]".parse().unwrap(),
+            selected_format: "<b>{name}</b> {percent}%[ ({muted})]".parse().unwrap(),
+            step: DEFAULT_STEP,
+            curve: Curve::default(),
+            max_volume: DEFAULT_MAX_VOLUME,
+        }
+    }
+}
+
+pub struct Applications {
+    format: Format,
+    stream_format: Format,
+    selected_format: Format,
+    stepping: Stepping,
+    /// Index of the stream clicks act on, if the user picked one.
+    selected: Cell<Option<u32>>,
+    /// Updated on the mainloop thread whenever a stream or sink changes.
+    state: Arc<Mutex<StreamsState>>,
+    #[allow(unused)]
+    subscription: Subscription,
+    context: Rc<RefCell<Context>>,
+    main_loop: Rc<SharedMainloop>,
+}
+
+#[derive(Default)]
+struct StreamsState {
+    streams: Vec<Stream>,
+    /// Indexes and descriptions of all sinks, in the order pulseaudio lists
+    /// them.
+    sinks: Vec<(u32, String)>,
+}
+
+/// A sink input, i.e. audio an application plays.
+#[derive(PartialEq)]
+struct Stream {
+    index: u32,
+    name: String,
+    sink: u32,
+    volume: ChannelVolumes,
+    muted: bool,
+    corked: bool,
+}
+
+impl Applications {
+    /// The stream clicks act on: the one picked, or else the first one
+    /// playing.
+    fn selected<'a>(&self, streams: &'a [Stream]) -> Option<&'a Stream> {
+        let picked = self
+            .selected
+            .get()
+            .and_then(|index| streams.iter().find(|stream| stream.index == index));
+
+        picked
+            .or_else(|| streams.iter().find(|stream| !stream.corked))
+            .or_else(|| streams.first())
+    }
+
+    fn select_next_stream(&self, streams: &[Stream]) {
+        let next = match self.selected(streams) {
+            Some(selected) => streams
+                .iter()
+                .position(|stream| stream.index == selected.index)
+                .map(|i| &streams[(i + 1) % streams.len()]),
+            None => None,
+        };
+
+        self.selected.set(next.map(|stream| stream.index));
+    }
+
+    /// Needs the mainloop locked.
+    fn move_to_next_sink(&self, stream: &Stream, sinks: &[(u32, String)]) {
+        let next = match sinks.iter().position(|(index, _)| *index == stream.sink) {
+            Some(i) => sinks[(i + 1) % sinks.len()].0,
+            None => match sinks.first() {
+                Some((index, _)) => *index,
+                None => return,
+            },
+        };
+
+        let name = stream.name.clone();
+        self.context.borrow().introspect().move_sink_input_by_index(
+            stream.index,
+            next,
+            Some(Box::new(move |success| {
+                if !success {
+                    error!("Cannot move {} to another sink", name);
+                }
+            })),
+        );
+    }
+}
+
+impl Block for Applications {
+    fn current_state(&mut self) -> Result<BlockState, BlockError> {
+        let state = self.state.lock().unwrap();
+        let selected = self.selected(&state.streams).map(|stream| stream.index);
+        let mut streams = Markup::new();
+
+        for (i, stream) in state.streams.iter().enumerate() {
+            let sink = state
+                .sinks
+                .iter()
+                .find(|(index, _)| *index == stream.sink)
+                .map(|(_, description)| description.as_str());
+            let values = volume_values(&stream.volume)
+                .with("name", stream.name.as_str())
+                .with_optional("sink", sink)
+                .with_optional("muted", if stream.muted { Some("muted") } else { None })
+                .with_optional("paused", if stream.corked { Some("paused") } else { None });
+            let format = if Some(stream.index) == selected {
+                &self.selected_format
+            } else {
+                &self.stream_format
+            };
+
+            if i > 0 {
+                streams = streams.push_text(" ");
+            }
+
+            streams = streams.push(&format.render(&values));
+        }
+
+        let values = Values::new()
+            .with("icon", Icon::Music.to_string())
+            .with("count", state.streams.len() as i64)
+            .with_optional(
+                "streams",
+                if state.streams.is_empty() {
+                    None
+                } else {
+                    Some(streams)
+                },
+            );
+
+        Ok(BlockState::from_markup(self.format.render(&values)))
+    }
+
+    fn handle_click(&self, event: ClickEvent) {
+        match event.button() {
+            MouseButton::Left => self.run_action("next_stream"),
+            MouseButton::Middle => self.run_action("toggle_mute"),
+            MouseButton::Right => self.run_action("move_to_next_sink"),
+            MouseButton::ScrollUp => self.run_action("volume_up"),
+            MouseButton::ScrollDown => self.run_action("volume_down"),
+            _ => {}
+        }
+    }
+
+    fn actions(&self) -> &'static [&'static str] {
+        &[
+            "next_stream",
+            "toggle_mute",
+            "volume_up",
+            "volume_down",
+            "move_to_next_sink",
+        ]
+    }
+
+    fn run_action(&self, action: &str) {
+        let _lock = MainloopLock::new(&self.main_loop);
+        let state = self.state.lock().unwrap();
+
+        if action == "next_stream" {
+            self.select_next_stream(&state.streams);
+            return;
+        }
+
+        let stream = match self.selected(&state.streams) {
+            Some(stream) => stream,
+            None => {
+                warn!("Ignoring {}, no application is playing", action);
+                return;
+            }
+        };
+
+        let mut introspector = self.context.borrow().introspect();
+
+        match action {
+            "toggle_mute" => {
+                introspector.set_sink_input_mute(stream.index, !stream.muted, None);
+            }
+            "move_to_next_sink" => self.move_to_next_sink(stream, &state.sinks),
+            _ => {
+                let channel_volumes = self.stepping.apply(&stream.volume, action);
+                introspector.set_sink_input_volume(stream.index, &channel_volumes, None);
+            }
+        }
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
     }
 }
 
+/// Reads all sink inputs, and redraws the block if any of them changed.
+fn query_streams(introspector: &Introspector, state: Arc<Mutex<StreamsState>>, notifier: Notifier) {
+    let mut streams = vec![];
+
+    introspector.get_sink_input_info_list(move |result| match result {
+        ListResult::Item(input) => streams.push(Stream {
+            index: input.index,
+            name: input
+                .proplist
+                .gets(properties::APPLICATION_NAME)
+                .or_else(|| input.name.as_deref().map(str::to_owned))
+                .unwrap_or_else(|| input.index.to_string()),
+            sink: input.sink,
+            volume: input.volume,
+            muted: input.mute,
+            corked: input.corked,
+        }),
+        ListResult::End => {
+            let mut state = state.lock().unwrap();
+
+            if state.streams != streams {
+                state.streams = std::mem::take(&mut streams);
+                notifier.notify();
+            }
+        }
+        ListResult::Error => warn!("Cannot list the pulse sink inputs"),
+    });
+}
+
+/// Reads the sinks streams can be moved to.
+fn query_stream_sinks(
+    introspector: &Introspector,
+    state: Arc<Mutex<StreamsState>>,
+    notifier: Notifier,
+) {
+    let mut sinks = vec![];
+
+    introspector.get_sink_info_list(move |result| match result {
+        ListResult::Item(sink_info) => {
+            let description = sink_info
+                .description
+                .as_deref()
+                .or(sink_info.name.as_deref())
+                .unwrap_or("")
+                .to_owned();
+
+            sinks.push((sink_info.index, description));
+        }
+        ListResult::End => {
+            let mut state = state.lock().unwrap();
+
+            if state.sinks != sinks {
+                state.sinks = std::mem::take(&mut sinks);
+                notifier.notify();
+            }
+        }
+        ListResult::Error => warn!("Cannot list the pulse sinks"),
+    });
+}
+
+/// Starts connecting, with the mainloop locked.
+fn connect(mainloop: &Mainloop) -> Result<Context, BlockError> {
+    let mut ctx = Context::new(mainloop, "stsbr")
+        .ok_or_else(|| BlockError::new("Failed to create pulse context".to_string()))?;
+    ctx.connect(None, libpulse_binding::context::flags::NOFLAGS, None)
+        .map_err(|e| BlockError::new(format!("Failed to connect to pulse: {}", e)))?;
+
+    Ok(ctx)
+}
+
 impl VolumeFactory {
-    pub fn new() -> Self {
-        let mut mainloop = Mainloop::new().unwrap();
-        mainloop.start().unwrap();
+    pub fn new() -> Result<Self, BlockError> {
+        let mut mainloop = Mainloop::new()
+            .ok_or_else(|| BlockError::new("Failed to create pulse mainloop".to_string()))?;
+        mainloop
+            .start()
+            .map_err(|e| BlockError::new(format!("Failed to start pulse mainloop: {}", e)))?;
+
+        mainloop.lock();
+        let ctx = connect(&mainloop);
+        mainloop.unlock();
 
-        let mut ctx = Context::new(&mainloop, "stsbr").unwrap();
-        ctx.connect(None, libpulse_binding::context::flags::NOFLAGS, None)
-            .unwrap();
+        let mut ctx = ctx?;
+        let main_loop = Rc::new(SharedMainloop {
+            main_loop: RefCell::new(mainloop),
+            locks: Cell::new(0),
+        });
 
         loop {
-            match ctx.get_state() {
+            let state = {
+                let _lock = MainloopLock::new(&main_loop);
+                ctx.get_state()
+            };
+
+            match state {
                 libpulse_binding::context::State::Ready => {
                     break;
                 }
                 libpulse_binding::context::State::Failed
                 | libpulse_binding::context::State::Terminated => {
-                    panic!("Failed to connect to pulse");
+                    let _lock = MainloopLock::new(&main_loop);
+                    drop(ctx);
+
+                    return Err(BlockError::new("Failed to connect to pulse".to_string()));
                 }
-                _ => {}
+                _ => std::thread::sleep(CONNECT_POLL_INTERVAL),
             }
         }
 
-        VolumeFactory {
-            context: Rc::new(ctx),
-            main_loop: Rc::new(mainloop),
+        let lock = MainloopLock::new(&main_loop);
+        let listeners: Listeners = Arc::new(Mutex::new(HashMap::new()));
+        let dispatched = listeners.clone();
+        let introspector = ctx.introspect();
+
+        ctx.set_subscribe_callback(Some(Box::new(move |facility, _, _| {
+            if let (Some(facility), Ok(mut listeners)) = (facility, dispatched.lock()) {
+                for listener in listeners.values_mut() {
+                    listener(&introspector, facility);
+                }
+            }
+        })));
+        let mask = subscription_masks::SINK
+            | subscription_masks::SOURCE
+            | subscription_masks::SINK_INPUT
+            | subscription_masks::SERVER;
+        ctx.subscribe(mask, |success| {
+            if !success {
+                error!("Cannot subscribe to pulse events, volume will not update");
+            }
+        });
+
+        drop(lock);
+
+        Ok(VolumeFactory {
+            context: Rc::new(RefCell::new(ctx)),
+            listeners,
+            next_listener: Cell::new(0),
+            main_loop,
+        })
+    }
+
+    fn subscribe(&self, listener: Listener) -> Subscription {
+        let id = self.next_listener.get();
+        self.next_listener.set(id + 1);
+
+        if let Ok(mut listeners) = self.listeners.lock() {
+            listeners.insert(id, listener);
+        }
+
+        Subscription {
+            id,
+            listeners: self.listeners.clone(),
         }
     }
 
-    pub fn new_volume(&self, sink_name: String) -> Volume {
+    pub fn new_volume(&self, config: VolumeConfig, notifier: Notifier) -> Volume {
+        let state = Arc::new(Mutex::new(SinkState::default()));
+        let _lock = MainloopLock::new(&self.main_loop);
+        let introspector = self.context.borrow().introspect();
+        query_sink(&introspector, &config.sink, state.clone(), notifier.clone());
+        query_sinks(&introspector, state.clone());
+        query_default_sink(&introspector, state.clone());
+
+        let subscription = {
+            let sink_name = config.sink.clone();
+            let state = state.clone();
+
+            // `@DEFAULT_SINK@` is resolved by pulseaudio on every query, so a
+            // new default is picked up by querying again.
+            self.subscribe(Box::new(move |introspector, facility| match facility {
+                Facility::Sink => {
+                    query_sink(introspector, &sink_name, state.clone(), notifier.clone());
+                    query_sinks(introspector, state.clone());
+                }
+                Facility::Server => {
+                    query_sink(introspector, &sink_name, state.clone(), notifier.clone());
+                    query_default_sink(introspector, state.clone());
+                }
+                _ => {}
+            }))
+        };
+
         Volume {
-            sink_name,
+            sink_name: config.sink,
+            format: config.format,
+            names: config.names,
+            stepping: Stepping::new(config.step, config.curve, config.max_volume),
+            state,
+            subscription,
             context: self.context.clone(),
             main_loop: self.main_loop.clone(),
         }
     }
+
+    pub fn new_microphone(&self, config: MicrophoneConfig, notifier: Notifier) -> Microphone {
+        let source = Arc::new(Mutex::new(None));
+        let _lock = MainloopLock::new(&self.main_loop);
+        let introspector = self.context.borrow().introspect();
+        query_source(
+            &introspector,
+            &config.source,
+            source.clone(),
+            notifier.clone(),
+        );
+
+        let subscription = {
+            let source_name = config.source.clone();
+            let source = source.clone();
+
+            // Server events include a new default source.
+            self.subscribe(Box::new(move |introspector, facility| {
+                if facility == Facility::Source || facility == Facility::Server {
+                    query_source(introspector, &source_name, source.clone(), notifier.clone());
+                }
+            }))
+        };
+
+        Microphone {
+            source_name: config.source,
+            format: config.format,
+            stepping: Stepping::new(config.step, config.curve, config.max_volume),
+            source,
+            subscription,
+            context: self.context.clone(),
+            main_loop: self.main_loop.clone(),
+        }
+    }
+
+    pub fn new_applications(&self, config: ApplicationsConfig, notifier: Notifier) -> Applications {
+        let state = Arc::new(Mutex::new(StreamsState::default()));
+        let _lock = MainloopLock::new(&self.main_loop);
+        let introspector = self.context.borrow().introspect();
+        query_streams(&introspector, state.clone(), notifier.clone());
+        query_stream_sinks(&introspector, state.clone(), notifier.clone());
+
+        let subscription = {
+            let state = state.clone();
+
+            self.subscribe(Box::new(move |introspector, facility| match facility {
+                Facility::SinkInput => query_streams(introspector, state.clone(), notifier.clone()),
+                Facility::Sink => query_stream_sinks(introspector, state.clone(), notifier.clone()),
+                _ => {}
+            }))
+        };
+
+        Applications {
+            format: config.format,
+            stream_format: config.stream_format,
+            selected_format: config.selected_format,
+            stepping: Stepping::new(config.step, config.curve, config.max_volume),
+            selected: Cell::new(None),
+            state,
+            subscription,
+            context: self.context.clone(),
+            main_loop: self.main_loop.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libpulse_binding::sample::CHANNELS_MAX;
+
+    fn percent(percent: u32) -> u32 {
+        VOLUME_NORM.0 * percent / 100
+    }
+
+    fn volumes(values: &[u32]) -> ChannelVolumes {
+        let mut volumes = ChannelVolumes {
+            channels: values.len() as u8,
+            values: [VOLUME_MUTED; CHANNELS_MAX],
+        };
+
+        for (volume, value) in volumes.values.iter_mut().zip(values) {
+            volume.0 = *value;
+        }
+
+        volumes
+    }
+
+    fn channels(volumes: &ChannelVolumes) -> Vec<u32> {
+        volumes.values[..usize::from(volumes.channels)]
+            .iter()
+            .map(|volume| volume.0)
+            .collect()
+    }
+
+    #[test]
+    fn scale_keeps_the_balance() {
+        let scaled = scale(
+            &volumes(&[percent(100), percent(50)]),
+            PulseVolume(percent(50)),
+        );
+
+        assert_eq!(channels(&scaled), vec![percent(50), percent(25)]);
+    }
+
+    #[test]
+    fn scale_raises_silent_channels_alike() {
+        let scaled = scale(&volumes(&[0, 0]), PulseVolume(1000));
+
+        assert_eq!(channels(&scaled), vec![1000, 1000]);
+    }
+
+    #[test]
+    fn scale_leaves_unused_channels_alone() {
+        let mut volume = volumes(&[percent(100)]);
+        volume.values[1].0 = 1234;
+
+        let scaled = scale(&volume, PulseVolume(percent(50)));
+
+        assert_eq!(channels(&scaled), vec![percent(50)]);
+        assert_eq!(scaled.values[1].0, 1234);
+    }
+
+    #[test]
+    fn steps_the_loudest_channel() {
+        let stepping = Stepping::new(25.0, Curve::Cubic, 150);
+        let volume = volumes(&[percent(50), percent(100)]);
+
+        assert_eq!(
+            channels(&stepping.apply(&volume, "volume_up")),
+            vec![VOLUME_NORM.0 * 5 / 8, percent(125)]
+        );
+        assert_eq!(
+            channels(&stepping.apply(&volume, "volume_down")),
+            vec![VOLUME_NORM.0 * 3 / 8, percent(75)]
+        );
+    }
+
+    #[test]
+    fn stops_at_the_max_volume() {
+        let stepping = Stepping::new(25.0, Curve::Cubic, 100);
+
+        assert_eq!(
+            channels(&stepping.apply(&volumes(&[percent(90)]), "volume_up")),
+            vec![percent(100)]
+        );
+    }
+
+    #[test]
+    fn never_lowers_a_volume_above_the_max_when_stepping_up() {
+        let stepping = Stepping::new(25.0, Curve::Cubic, 100);
+
+        assert_eq!(
+            channels(&stepping.apply(&volumes(&[percent(120)]), "volume_up")),
+            vec![percent(120)]
+        );
+    }
+
+    #[test]
+    fn stops_at_silence() {
+        let volume = volumes(&[percent(10)]);
+
+        for curve in &[Curve::Cubic, Curve::Linear, Curve::Db] {
+            let stepping = Stepping::new(25.0, *curve, 100);
+
+            assert_eq!(
+                channels(&stepping.apply(&volume, "volume_down")),
+                vec![VOLUME_MUTED.0],
+                "{:?}",
+                curve
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_steps_that_are_not_positive() {
+        for step in &["0.0", "-5.0", "nan", "inf"] {
+            let config = format!("step = {}", step);
+
+            assert!(toml::from_str::<VolumeConfig>(&config).is_err(), "{}", step);
+        }
+
+        assert_eq!(
+            toml::from_str::<VolumeConfig>("step = 2.5").unwrap().step,
+            2.5
+        );
+    }
+
+    #[test]
+    fn shows_silence_at_the_db_floor() {
+        let format: Format = "{db}".parse().unwrap();
+        let values = volume_values(&volumes(&[VOLUME_MUTED.0]));
+
+        assert_eq!(format.render(&values).to_plain_text(), "-60");
+    }
 }
@@ -0,0 +1,203 @@
+use crate::socket_path::default_socket_path;
+use log::LevelFilter;
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: stsbr [OPTIONS]
+
+Options:
+    -c, --config <PATH>      Configuration file to use
+        --log-file <PATH>    File to write the log to, `-` for stderr
+        --log-level <LEVEL>  One of off, error, warn, info, debug, trace
+    -o, --output <OUTPUT>    One of i3bar, swaybar, lemonbar, tmux, plain
+        --check-config       Validate the configuration and exit
+        --socket <PATH>      Control socket to listen on for stsbr-msg
+    -V, --version            Print the version and exit
+    -h, --help               Print this help and exit
+";
+
+pub enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+pub struct Options {
+    pub config: PathBuf,
+    /// `None` logs to stderr.
+    pub log_file: Option<PathBuf>,
+    pub log_level: LevelFilter,
+    /// Overrides the output set in the configuration file.
+    pub output: Option<String>,
+    pub check_config: bool,
+    /// `None` without `--socket` or a runtime directory to put it in.
+    pub socket: Option<PathBuf>,
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut config = None;
+    let mut log_file = Some(default_log_path());
+    let mut log_level = LevelFilter::Info;
+    let mut output = None;
+    let mut check_config = false;
+    let mut socket = None;
+
+    while let Some(arg) = args.next() {
+        // Accept both `--option value` and `--option=value`.
+        let (flag, inline_value) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => {
+                (arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {}", flag))
+        };
+
+        match flag.as_str() {
+            "-c" | "--config" => config = Some(PathBuf::from(value()?)),
+            "--log-file" => {
+                log_file = match value()?.as_str() {
+                    "-" => None,
+                    path => Some(PathBuf::from(path)),
+                }
+            }
+            "--log-level" => {
+                let level = value()?;
+                log_level = level
+                    .parse()
+                    .map_err(|_| format!("invalid log level `{}`", level))?;
+            }
+            "-o" | "--output" => output = Some(value()?),
+            "--check-config" => check_config = true,
+            "--socket" => socket = Some(PathBuf::from(value()?)),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-h" | "--help" => return Ok(Command::Help),
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    Ok(Command::Run(Options {
+        config: config.unwrap_or_else(default_config_path),
+        log_file,
+        log_level,
+        output,
+        check_config,
+        socket: socket.or_else(default_socket_path),
+    }))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+/// Resolves a leading `~/` in paths from the config to the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(fallback)))
+}
+
+/// `$XDG_CONFIG_HOME/stsbr/config.toml`, unless only one of the older
+/// locations, `~/.stsbr.toml` or `.stsbr.toml` in the working directory, exists.
+fn default_config_path() -> PathBuf {
+    let xdg_path = xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("stsbr/config.toml"));
+    let candidates = vec![
+        xdg_path.clone(),
+        home_dir().map(|home| home.join(".stsbr.toml")),
+        Some(PathBuf::from(".stsbr.toml")),
+    ];
+
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|path| path.is_file())
+        .or(xdg_path)
+        .unwrap_or_else(|| PathBuf::from(".stsbr.toml"))
+}
+
+/// `$XDG_CACHE_HOME/stsbr/stsbr.log`.
+fn default_log_path() -> PathBuf {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+        .map(|dir| dir.join("stsbr/stsbr.log"))
+        .unwrap_or_else(|| PathBuf::from("stsbr.log"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn options(args: &[&str]) -> Options {
+        match parse(args) {
+            Ok(Command::Run(options)) => options,
+            Ok(_) => panic!("{:?} did not run the bar", args),
+            Err(e) => panic!("{:?} failed: {}", args, e),
+        }
+    }
+
+    #[test]
+    fn parses_options_with_separate_and_inline_values() {
+        let options = options(&[
+            "-c",
+            "bar.toml",
+            "--log-file=-",
+            "--log-level",
+            "debug",
+            "--output=tmux",
+            "--check-config",
+            "--socket",
+            "/run/bar.sock",
+        ]);
+
+        assert_eq!(options.config, PathBuf::from("bar.toml"));
+        assert_eq!(options.log_file, None);
+        assert_eq!(options.log_level, LevelFilter::Debug);
+        assert_eq!(options.output.as_deref(), Some("tmux"));
+        assert!(options.check_config);
+        assert_eq!(options.socket, Some(PathBuf::from("/run/bar.sock")));
+    }
+
+    #[test]
+    fn keeps_equals_signs_in_short_option_values() {
+        assert_eq!(
+            options(&["-c", "a=b.toml"]).config,
+            PathBuf::from("a=b.toml")
+        );
+    }
+
+    #[test]
+    fn stops_at_help_and_version() {
+        assert!(matches!(parse(&["-h", "--unknown"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["--version"]), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let invalid: [&[&str]; 4] = [
+            &["--config"],
+            &["--log-level", "loud"],
+            &["--unknown"],
+            &["-c=bar.toml"],
+        ];
+
+        for args in invalid.iter() {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
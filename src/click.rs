@@ -0,0 +1,259 @@
+use crate::block::{ClickEvent, Modifier, MouseButton};
+use crate::error::Error;
+use serde::Deserialize;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use toml::Value;
+
+/// The config keys binding each button.
+pub const CLICK_KEYS: [(&str, MouseButton); 5] = [
+    ("on_click", MouseButton::Left),
+    ("on_middle_click", MouseButton::Middle),
+    ("on_right_click", MouseButton::Right),
+    ("on_scroll_up", MouseButton::ScrollUp),
+    ("on_scroll_down", MouseButton::ScrollDown),
+];
+
+/// Understood by every block: just refreshes it.
+pub const REFRESH_ACTION: &str = "refresh";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClickAction {
+    /// Run with `sh -c`, without waiting for it.
+    Command(String),
+    /// One of the actions the block offers.
+    Builtin(String),
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    button: MouseButton,
+    /// All of them have to be held, so the binding with most modifiers wins.
+    modifiers: Vec<Modifier>,
+    action: ClickAction,
+}
+
+/// What clicks on a block do instead of its own click handling.
+#[derive(Debug, Clone, Default)]
+pub struct ClickBindings {
+    bindings: Vec<Binding>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BindingsConfig {
+    Command(String),
+    Binding(BindingConfig),
+    Bindings(Vec<BindingConfig>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingConfig {
+    #[serde(default)]
+    modifiers: Vec<Modifier>,
+    command: Option<String>,
+    action: Option<String>,
+}
+
+impl ClickBindings {
+    /// Reads the `on_*` keys of a block section. Each is a shell command, or
+    /// a table with either `command` or `action` and optional `modifiers`, or
+    /// an array of such tables.
+    pub fn from_section(section: &Value) -> Result<ClickBindings, Error> {
+        let mut bindings = vec![];
+
+        for (key, button) in CLICK_KEYS.iter() {
+            let config = match section.get(*key) {
+                Some(value) => value.clone().try_into().map_err(|_| {
+                    Error::config_key(
+                        key,
+                        "expected a command, or tables with `command` or `action` and optional \
+                         `modifiers`"
+                            .into(),
+                    )
+                })?,
+                None => continue,
+            };
+
+            let configs = match config {
+                BindingsConfig::Command(command) => {
+                    bindings.push(Binding {
+                        button: *button,
+                        modifiers: vec![],
+                        action: ClickAction::Command(command),
+                    });
+                    continue;
+                }
+                BindingsConfig::Binding(config) => vec![config],
+                BindingsConfig::Bindings(configs) => configs,
+            };
+
+            for config in configs {
+                let action = match (config.command, config.action) {
+                    (Some(command), None) => ClickAction::Command(command),
+                    (None, Some(action)) => ClickAction::Builtin(action),
+                    _ => {
+                        return Err(Error::config_key(
+                            key,
+                            "expected either `command` or `action`".into(),
+                        ))
+                    }
+                };
+
+                bindings.push(Binding {
+                    button: *button,
+                    modifiers: config.modifiers,
+                    action,
+                });
+            }
+        }
+
+        Ok(ClickBindings { bindings })
+    }
+
+    /// Fails for built-in actions the block does not offer.
+    pub fn check_actions(&self, actions: &[&str]) -> Result<(), Error> {
+        for binding in self.bindings.iter() {
+            if let ClickAction::Builtin(action) = &binding.action {
+                if action != REFRESH_ACTION && !actions.contains(&action.as_str()) {
+                    let key = CLICK_KEYS
+                        .iter()
+                        .find(|(_, button)| *button == binding.button)
+                        .map_or("on_click", |(key, _)| key);
+                    let mut known = vec![REFRESH_ACTION];
+                    known.extend_from_slice(actions);
+
+                    return Err(Error::config_key(
+                        key,
+                        format!(
+                            "unknown action `{}`, this block has {}",
+                            action,
+                            known.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The action bound to the button, with all of its modifiers held.
+    pub fn find(&self, event: &ClickEvent) -> Option<&ClickAction> {
+        // Reversed, so that of equally specific bindings the first one wins.
+        self.bindings
+            .iter()
+            .rev()
+            .filter(|binding| binding.button == event.button())
+            .filter(|binding| {
+                binding
+                    .modifiers
+                    .iter()
+                    .all(|modifier| event.modifiers().contains(modifier))
+            })
+            .max_by_key(|binding| binding.modifiers.len())
+            .map(|binding| &binding.action)
+    }
+}
+
+/// Starts a command bound to a click. It is reaped in the background, the
+/// bar doesn't wait for it. It gets its own process group, or the signals the
+/// bar sends to ours when it hides would kill it.
+pub fn spawn_command(command: &str) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .process_group(0)
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => error!("Cannot run `{}`: {}", command, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(section: &str) -> Result<ClickBindings, Error> {
+        ClickBindings::from_section(&section.parse().unwrap())
+    }
+
+    fn click(button: MouseButton, modifiers: Vec<Modifier>) -> ClickEvent {
+        ClickEvent::new(button, "volume".into()).with_modifiers(modifiers)
+    }
+
+    #[test]
+    fn prefers_the_binding_with_most_modifiers() {
+        let bindings = bindings(
+            r#"
+            on_click = [
+                { action = "toggle_mute" },
+                { modifiers = ["Shift"], command = "pavucontrol" },
+                { modifiers = ["Shift", "Control"], action = "next_sink" },
+            ]
+            on_right_click = "pavucontrol"
+            "#,
+        )
+        .unwrap();
+        let action = |button, modifiers| bindings.find(&click(button, modifiers)).cloned();
+
+        assert_eq!(
+            action(MouseButton::Left, vec![]),
+            Some(ClickAction::Builtin("toggle_mute".into()))
+        );
+        assert_eq!(
+            action(MouseButton::Left, vec![Modifier::Shift, Modifier::Mod2]),
+            Some(ClickAction::Command("pavucontrol".into()))
+        );
+        assert_eq!(
+            action(MouseButton::Left, vec![Modifier::Control, Modifier::Shift]),
+            Some(ClickAction::Builtin("next_sink".into()))
+        );
+        assert_eq!(
+            action(MouseButton::Right, vec![Modifier::Shift]),
+            Some(ClickAction::Command("pavucontrol".into()))
+        );
+        assert_eq!(action(MouseButton::Middle, vec![]), None);
+    }
+
+    #[test]
+    fn prefers_the_first_of_equally_specific_bindings() {
+        let bindings = bindings(
+            r#"
+            on_click = [
+                { modifiers = ["Shift"], action = "first" },
+                { modifiers = ["Control"], action = "second" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let event = click(MouseButton::Left, vec![Modifier::Shift, Modifier::Control]);
+
+        assert_eq!(
+            bindings.find(&event),
+            Some(&ClickAction::Builtin("first".into()))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_bindings() {
+        let invalid = [
+            "on_click = 5",
+            "on_click = { modifiers = [\"Shift\"] }",
+            "on_click = { command = \"true\", action = \"refresh\" }",
+            "on_click = { modifiers = [\"Super\"], action = \"refresh\" }",
+            "on_click = { action = \"refresh\", button = 1 }",
+        ];
+
+        for section in invalid.iter() {
+            assert!(bindings(section).is_err(), "{}", section);
+        }
+    }
+}
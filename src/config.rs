@@ -0,0 +1,333 @@
+use crate::block::{Block, Notifier};
+use crate::error::Error;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use toml::Value;
+
+/// Keys every section understands, whatever its module.
+const COMMON_KEYS: &[&str] = &[
+    "module",
+    "name",
+    "interval",
+    "theme",
+    "min_width",
+    "align",
+    "on_click",
+    "on_middle_click",
+    "on_right_click",
+    "on_scroll_up",
+    "on_scroll_down",
+];
+
+pub fn read_config(path: &Path) -> Result<Value, Error> {
+    let mut config = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut config))
+        .map_err(|e| Error::config(format!("Cannot read {}: {}", path.display(), e)))?;
+
+    config
+        .parse::<Value>()
+        .map_err(|e| Error::config(e.to_string()))
+}
+
+/// Called with the name and section of a block.
+pub type SectionCallback<'a> = Box<dyn 'a + FnMut(&str, &Value)>;
+
+/// Calls `on_section` for every configured block, in the order they appear
+/// on the bar.
+pub fn parse_config<'a>(
+    config: &Value,
+    mut on_section: SectionCallback<'a>,
+) -> Result<(), Error> {
+    match (config.get("block"), config.get("sources")) {
+        (Some(Value::Array(blocks)), None) => {
+            for (name, section) in assign_names(blocks)?.iter().zip(blocks) {
+                on_section(name, section);
+            }
+
+            Ok(())
+        }
+        (Some(_), None) => Err(Error::config_key(
+            "block",
+            "expected an array of tables, written as [[block]]".into(),
+        )),
+        (None, Some(Value::Table(toml_sources))) => {
+            warn!("[sources.*] is deprecated, use [[block]] to control the order of blocks");
+
+            // Table keys are sorted, so this has always been reverse alphabetical order.
+            for (name, section) in toml_sources.iter().rev() {
+                on_section(name, section);
+            }
+
+            Ok(())
+        }
+        (None, Some(_)) => Err(Error::config_key("sources", "expected a table".into())),
+        (Some(_), Some(_)) => Err(Error::config(
+            "both [[block]] and [sources.*] are present, move all blocks to [[block]]".into(),
+        )),
+        (None, None) => Err(Error::config(
+            "no blocks configured, add a [[block]]".into(),
+        )),
+    }
+}
+
+/// Gives every block a unique name: the one set with `name`, or else its
+/// module, numbered from the second block of that module on. Names only
+/// change when blocks of the same module are reordered.
+fn assign_names(blocks: &[Value]) -> Result<Vec<String>, Error> {
+    let mut used_names = HashSet::new();
+
+    for (i, section) in blocks.iter().enumerate() {
+        if section.get("name").is_some() {
+            let name =
+                get_str(section, "name").map_err(|e| e.in_section(&format!("block {}", i + 1)))?;
+
+            if !used_names.insert(name.to_owned()) {
+                return Err(Error::config_key(
+                    "name",
+                    format!("more than one block is named `{}`", name),
+                )
+                .in_section(&format!("block {}", i + 1)));
+            }
+        }
+    }
+
+    let mut names = vec![];
+
+    for section in blocks {
+        let name = match section.get("name").and_then(Value::as_str) {
+            Some(name) => name.to_owned(),
+            None => {
+                let module = section
+                    .get("module")
+                    .and_then(Value::as_str)
+                    .unwrap_or("block");
+                let name = (1..)
+                    .map(|n| match n {
+                        1 => module.to_owned(),
+                        _ => format!("{}_{}", module, n),
+                    })
+                    .find(|name| !used_names.contains(name))
+                    .unwrap();
+
+                used_names.insert(name.clone());
+                name
+            }
+        };
+
+        names.push(name);
+    }
+
+    Ok(names)
+}
+
+pub fn get_str<'a>(section: &'a Value, key: &str) -> Result<&'a str, Error> {
+    match section.get(key) {
+        Some(Value::String(value)) => Ok(value),
+        Some(_) => Err(Error::config_key(key, "expected a string".into())),
+        None => Err(Error::config_key(key, "missing".into())),
+    }
+}
+
+/// Longer intervals are surely mistakes, and would overflow the clock.
+const MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub fn parse_interval(value: &Value) -> Result<Duration, Error> {
+    let interval = match value {
+        Value::Integer(seconds) if *seconds > 0 => Some(Duration::from_secs(*seconds as u64)),
+        Value::Float(seconds) if *seconds > 0.0 => Duration::try_from_secs_f64(*seconds).ok(),
+        _ => None,
+    };
+
+    match interval {
+        Some(interval) if interval <= MAX_INTERVAL => Ok(interval),
+        _ => Err(Error::config_key(
+            "interval",
+            format!(
+                "expected a positive number of seconds up to {}, got {}",
+                MAX_INTERVAL.as_secs(),
+                value
+            ),
+        )),
+    }
+}
+
+/// Deserializes the module specific settings of a section into the block's
+/// config struct.
+pub fn parse_block_config<C: DeserializeOwned>(section: &Value) -> Result<C, Error> {
+    section
+        .clone()
+        .try_into()
+        .map_err(|e| Error::config(e.to_string()))
+}
+
+type CheckFn = Box<dyn Fn(&Value) -> Result<(), Error>>;
+type CreateFn = Box<dyn Fn(&Value, Notifier) -> Result<Box<dyn Block>, Error>>;
+type IgnoredKeysFn = Box<dyn Fn(&Value) -> Vec<String>>;
+
+/// Creates blocks of one module from their config sections.
+pub struct BlockFactory {
+    check: CheckFn,
+    create: CreateFn,
+    ignored_keys: IgnoredKeysFn,
+}
+
+impl BlockFactory {
+    pub fn new<C, F>(create: F) -> Self
+    where
+        C: DeserializeOwned + 'static,
+        F: Fn(C, Notifier) -> Result<Box<dyn Block>, Error> + 'static,
+    {
+        BlockFactory {
+            check: Box::new(|section| parse_block_config::<C>(section).map(|_| ())),
+            create: Box::new(move |section, notifier| {
+                create(parse_block_config(section)?, notifier)
+            }),
+            ignored_keys: Box::new(ignored_keys::<C>),
+        }
+    }
+
+    /// Validates a section without creating the block.
+    pub fn check(&self, section: &Value) -> Result<(), Error> {
+        (self.check)(section)
+    }
+
+    pub fn create(&self, section: &Value, notifier: Notifier) -> Result<Box<dyn Block>, Error> {
+        (self.create)(section, notifier)
+    }
+
+    /// Keys in the section that neither the module nor the loader know about.
+    pub fn unknown_keys(&self, section: &Value) -> Vec<String> {
+        (self.ignored_keys)(section)
+            .into_iter()
+            .filter(|key| !COMMON_KEYS.contains(&key.as_str()))
+            .collect()
+    }
+}
+
+/// Paths of the keys that deserializing the section into `C` skips over.
+fn ignored_keys<C: DeserializeOwned>(section: &Value) -> Vec<String> {
+    let mut keys = vec![];
+    let _: Result<C, _> = serde_ignored::deserialize(section.clone(), |path| {
+        keys.push(path.to_string());
+    });
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::date_time::DateTime;
+
+    fn blocks(config: &str) -> Vec<Value> {
+        match config.parse::<Value>().unwrap().get("block") {
+            Some(Value::Array(blocks)) => blocks.clone(),
+            _ => panic!("no blocks in {}", config),
+        }
+    }
+
+    #[test]
+    fn parses_intervals_in_seconds() {
+        assert_eq!(
+            parse_interval(&Value::Integer(5)).unwrap(),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            parse_interval(&Value::Float(0.5)).unwrap(),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_intervals() {
+        let invalid = vec![
+            Value::Integer(0),
+            Value::Integer(-1),
+            Value::Integer(24 * 60 * 60 + 1),
+            Value::Float(f64::NAN),
+            Value::Float(f64::INFINITY),
+            Value::Float(1e30),
+            Value::String("5".into()),
+        ];
+
+        for value in &invalid {
+            assert!(parse_interval(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn reports_keys_the_module_ignores() {
+        let factory = BlockFactory::new(|config, _| Ok(Box::new(DateTime::new(config))));
+        let section: Value = r#"
+            module = "test"
+            interval = 5
+            format = "{time}"
+            fromat = "{date}"
+            "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(factory.unknown_keys(&section), vec!["fromat"]);
+    }
+
+    #[test]
+    fn numbers_blocks_of_the_same_module() {
+        let blocks = blocks(
+            r#"
+            [[block]]
+            module = "volume"
+            [[block]]
+            module = "date_time"
+            [[block]]
+            module = "volume"
+            "#,
+        );
+
+        assert_eq!(
+            assign_names(&blocks).unwrap(),
+            vec!["volume", "date_time", "volume_2"]
+        );
+    }
+
+    #[test]
+    fn keeps_configured_names_free() {
+        let blocks = blocks(
+            r#"
+            [[block]]
+            module = "volume"
+            [[block]]
+            module = "volume"
+            name = "volume"
+            "#,
+        );
+
+        assert_eq!(assign_names(&blocks).unwrap(), vec!["volume_2", "volume"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let blocks = blocks(
+            r#"
+            [[block]]
+            module = "volume"
+            name = "speakers"
+            [[block]]
+            module = "microphone"
+            name = "speakers"
+            "#,
+        );
+        let error = assign_names(&blocks).unwrap_err().to_string();
+
+        assert_eq!(
+            error,
+            "Configuration error in section `block 2` at key `name`: \
+             more than one block is named `speakers`"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::scheduler::{Message, RenderedBlock};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// How long a client waits for the main loop to answer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client may take to send its request or read the reply.
+/// Connections are served one at a time, so a stuck client would block all
+/// others.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A command sent by stsbr-msg, one line per connection:
+///
+/// - `refresh [BLOCK]` refreshes one block, or all of them,
+/// - `action BLOCK ACTION` runs one of the actions a block offers,
+/// - `query` returns the states of all blocks as JSON.
+///
+/// The reply is a single line as well, either `ok`, the JSON, or `error: `
+/// followed by a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlRequest {
+    Refresh(Option<String>),
+    Action { block: String, action: String },
+    Query,
+}
+
+pub type Reply = Result<String, String>;
+
+impl ControlRequest {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["refresh"] => Ok(ControlRequest::Refresh(None)),
+            ["refresh", block] => Ok(ControlRequest::Refresh(Some((*block).to_owned()))),
+            ["action", block, action] => Ok(ControlRequest::Action {
+                block: (*block).to_owned(),
+                action: (*action).to_owned(),
+            }),
+            ["query"] => Ok(ControlRequest::Query),
+            _ => Err(format!("invalid request `{}`", line.trim())),
+        }
+    }
+}
+
+/// Listens for stsbr-msg on a Unix socket and hands its requests to the main
+/// loop. Fails if another bar is already listening on the same path.
+pub fn create_control_socket(path: &Path, sender: Sender<Message>) -> io::Result<()> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another instance is listening on it",
+        ));
+    }
+
+    // Left behind by an instance that didn't exit cleanly. Anything else at
+    // the path, like a file given by mistake, is kept.
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "not a socket, refusing to replace it",
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| handle_connection(stream, &sender));
+
+            if let Err(e) = result {
+                warn!("Control connection failed: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, sender: &Sender<Message>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let reply = match ControlRequest::parse(&line) {
+        Ok(request) => {
+            let (reply_sender, reply_receiver) = std::sync::mpsc::channel();
+
+            if sender
+                .send(Message::Control(request, reply_sender))
+                .is_err()
+            {
+                return Ok(());
+            }
+
+            reply_receiver
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| Err("the bar did not answer".into()))
+        }
+        Err(e) => Err(e),
+    };
+
+    match reply {
+        Ok(reply) => writeln!(&stream, "{}", reply),
+        Err(e) => writeln!(&stream, "error: {}", e),
+    }
+}
+
+/// The answer to `query`: what every block shows, as plain text.
+pub fn query_reply(blocks: &[RenderedBlock]) -> String {
+    let blocks: Vec<serde_json::Value> = blocks
+        .iter()
+        .map(|block| {
+            serde_json::json!({
+                "name": block.name,
+                "module": block.module,
+                "full_text": block.state.text().to_plain_text(),
+                "short_text": block.state.short_text().map(|text| text.to_plain_text()),
+                "color": block.state.color(),
+                "background": block.state.background(),
+                "urgent": block.state.urgent().unwrap_or(false),
+                "severity": block.state.severity(),
+            })
+        })
+        .collect();
+
+    serde_json::Value::Array(blocks).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            ControlRequest::parse("refresh\n"),
+            Ok(ControlRequest::Refresh(None))
+        );
+        assert_eq!(
+            ControlRequest::parse("refresh volume"),
+            Ok(ControlRequest::Refresh(Some("volume".into())))
+        );
+        assert_eq!(
+            ControlRequest::parse("  action volume   volume_up "),
+            Ok(ControlRequest::Action {
+                block: "volume".into(),
+                action: "volume_up".into(),
+            })
+        );
+        assert_eq!(ControlRequest::parse("query"), Ok(ControlRequest::Query));
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        for line in &["", "Refresh", "refresh a b", "action volume", "query all"] {
+            assert!(ControlRequest::parse(line).is_err(), "{:?}", line);
+        }
+    }
+}
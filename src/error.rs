@@ -0,0 +1,83 @@
+use crate::block::BlockError;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    Config {
+        section: Option<String>,
+        key: Option<String>,
+        message: String,
+    },
+    Protocol(String),
+    Block(BlockError),
+}
+
+impl std::error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Error::Config {
+                section,
+                key,
+                message,
+            } => {
+                f.write_str("Configuration error")?;
+
+                if let Some(section) = section {
+                    write!(f, " in section `{}`", section)?;
+                }
+
+                if let Some(key) = key {
+                    write!(f, " at key `{}`", key)?;
+                }
+
+                write!(f, ": {}", message)
+            }
+            Error::Protocol(message) => write!(f, "Protocol error: {}", message),
+            Error::Block(e) => write!(f, "Block error: {}", e),
+        }
+    }
+}
+
+impl From<BlockError> for Error {
+    fn from(e: BlockError) -> Self {
+        Error::Block(e)
+    }
+}
+
+impl Error {
+    pub fn config(message: String) -> Self {
+        Error::Config {
+            section: None,
+            key: None,
+            message,
+        }
+    }
+
+    pub fn config_key(key: &str, message: String) -> Self {
+        Error::Config {
+            section: None,
+            key: Some(key.into()),
+            message,
+        }
+    }
+
+    /// Attaches the name of the config section the error happened in.
+    pub fn in_section(self, name: &str) -> Self {
+        match self {
+            Error::Config { key, message, .. } => Error::Config {
+                section: Some(name.into()),
+                key,
+                message,
+            },
+            Error::Block(e) => Error::Config {
+                section: Some(name.into()),
+                key: None,
+                message: e.to_string(),
+            },
+            other => other,
+        }
+    }
+}
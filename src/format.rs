@@ -0,0 +1,459 @@
+use crate::markup::{escape, Markup};
+use serde::{de, Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// One value a block publishes for its format string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    /// Inserted as it is, while all other values are escaped.
+    Markup(Markup),
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_owned())
+    }
+}
+
+impl From<Markup> for Value {
+    fn from(markup: Markup) -> Self {
+        Value::Markup(markup)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(number: i64) -> Self {
+        Value::Integer(number)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(number: u32) -> Self {
+        Value::Integer(i64::from(number))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Value::Float(number)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(number: f32) -> Self {
+        Value::Float(f64::from(number))
+    }
+}
+
+/// The named values a block renders its format string with. Values that are
+/// not known at the moment are simply left out.
+#[derive(Debug, Clone, Default)]
+pub struct Values(HashMap<&'static str, Value>);
+
+impl Values {
+    pub fn new() -> Self {
+        Values(HashMap::new())
+    }
+
+    pub fn with<V: Into<Value>>(mut self, name: &'static str, value: V) -> Self {
+        self.0.insert(name, value.into());
+        self
+    }
+
+    pub fn with_optional<V: Into<Value>>(self, name: &'static str, value: Option<V>) -> Self {
+        match value {
+            Some(value) => self.with(name, value),
+            None => self,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<Align>,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Text(String),
+    Placeholder {
+        name: String,
+        spec: Spec,
+    },
+    /// Only rendered when all of its placeholders have a value.
+    Section(Vec<Token>),
+}
+
+/// A user-defined template for the text of a block.
+///
+/// `{name}` is replaced by the value called `name`, and `{name:spec}`
+/// formats it first: `spec` is `[[fill]align][0][width][.precision]`, with
+/// `<`, `>` or `^` as the alignment, much like Rust's own `format!`. Text in
+/// `[` and `]` is dropped unless every placeholder in it has a value, and a
+/// backslash makes the following character literal.
+///
+/// The template itself is Pango markup, so it can make parts bold or color
+/// them, while the values are escaped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Format {
+    tokens: Vec<Token>,
+}
+
+impl Format {
+    pub fn render(&self, values: &Values) -> Markup {
+        let mut output = String::new();
+        render_tokens(&self.tokens, values, &mut output);
+
+        Markup::trusted(output)
+    }
+}
+
+/// Appends the rendered tokens to `output` and tells whether every
+/// placeholder among them had a value.
+fn render_tokens(tokens: &[Token], values: &Values, output: &mut String) -> bool {
+    let mut complete = true;
+
+    for token in tokens {
+        match token {
+            Token::Text(text) => output.push_str(text),
+            Token::Placeholder { name, spec } => match values.get(name) {
+                Some(value) => output.push_str(&render_value(value, spec)),
+                None => complete = false,
+            },
+            Token::Section(tokens) => {
+                let mut section = String::new();
+
+                if render_tokens(tokens, values, &mut section) {
+                    output.push_str(&section);
+                }
+            }
+        }
+    }
+
+    complete
+}
+
+fn render_value(value: &Value, spec: &Spec) -> String {
+    let (text, default_align): (String, _) = match (value, spec.precision) {
+        (Value::Markup(markup), _) => {
+            let length = markup.to_plain_text().chars().count();
+
+            return pad(markup.as_str().to_owned(), length, spec, Align::Left);
+        }
+        (Value::Text(text), Some(precision)) => {
+            (text.chars().take(precision).collect(), Align::Left)
+        }
+        (Value::Text(text), None) => (text.clone(), Align::Left),
+        (Value::Integer(number), _) => (number.to_string(), Align::Right),
+        (Value::Float(number), Some(precision)) => {
+            (format!("{:.*}", precision, number), Align::Right)
+        }
+        (Value::Float(number), None) => (number.to_string(), Align::Right),
+    };
+
+    pad(escape(&text), text.chars().count(), spec, default_align)
+}
+
+/// Pads markup that shows `length` characters to the width of the spec.
+fn pad(markup: String, length: usize, spec: &Spec, default_align: Align) -> String {
+    let padding = match spec.width {
+        Some(width) if width > length => width - length,
+        _ => return markup,
+    };
+
+    let fill = escape(&spec.fill.unwrap_or(' ').to_string());
+    let (before, after) = match spec.align.unwrap_or(default_align) {
+        Align::Left => (0, padding),
+        Align::Right => (padding, 0),
+        Align::Center => (padding / 2, padding - padding / 2),
+    };
+
+    format!("{}{}{}", fill.repeat(before), markup, fill.repeat(after))
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        let mut chars = format.chars();
+        let tokens = parse_tokens(&mut chars, false)?;
+
+        Ok(Format { tokens })
+    }
+}
+
+fn parse_tokens(chars: &mut std::str::Chars, in_section: bool) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut text = String::new();
+
+    loop {
+        let c = match chars.next() {
+            Some(c) => c,
+            None if in_section => return Err("unclosed `[`".into()),
+            None => break,
+        };
+
+        match c {
+            '\\' => text.push(chars.next().ok_or("trailing `\\`")?),
+            '{' => {
+                flush_text(&mut text, &mut tokens);
+                tokens.push(parse_placeholder(chars)?);
+            }
+            '[' => {
+                flush_text(&mut text, &mut tokens);
+                tokens.push(Token::Section(parse_tokens(chars, true)?));
+            }
+            ']' if in_section => break,
+            ']' => return Err("unmatched `]`".into()),
+            '}' => return Err("unmatched `}`".into()),
+            c => text.push(c),
+        }
+    }
+
+    flush_text(&mut text, &mut tokens);
+
+    Ok(tokens)
+}
+
+fn flush_text(text: &mut String, tokens: &mut Vec<Token>) {
+    if !text.is_empty() {
+        tokens.push(Token::Text(std::mem::take(text)));
+    }
+}
+
+fn parse_placeholder(chars: &mut std::str::Chars) -> Result<Token, String> {
+    let mut placeholder = String::new();
+
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some(c) => placeholder.push(c),
+            None => return Err("unclosed `{`".into()),
+        }
+    }
+
+    let mut parts = placeholder.splitn(2, ':');
+    let name = parts.next().unwrap_or("").trim();
+
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("invalid placeholder `{{{}}}`", placeholder));
+    }
+
+    let spec = match parts.next() {
+        Some(spec) => parse_spec(spec).map_err(|e| format!("{} in `{{{}}}`", e, placeholder))?,
+        None => Spec::default(),
+    };
+
+    Ok(Token::Placeholder {
+        name: name.to_owned(),
+        spec,
+    })
+}
+
+/// Widths and precisions beyond this are surely mistakes, and would only
+/// waste memory on padding.
+const MAX_WIDTH: usize = 1000;
+
+fn parse_spec(spec: &str) -> Result<Spec, String> {
+    fn align(c: char) -> Option<Align> {
+        match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        }
+    }
+
+    let chars: Vec<char> = spec.chars().collect();
+    let mut result = Spec::default();
+    let mut i = 0;
+
+    if let Some(a) = chars.get(1).and_then(|c| align(*c)) {
+        result.fill = Some(chars[0]);
+        result.align = Some(a);
+        i = 2;
+    } else if let Some(a) = chars.first().and_then(|c| align(*c)) {
+        result.align = Some(a);
+        i = 1;
+    } else if chars.first() == Some(&'0') {
+        result.fill = Some('0');
+        result.align = Some(Align::Right);
+        i = 1;
+    }
+
+    let (width, next) = parse_number(&chars, i)?;
+    result.width = width;
+    i = next;
+
+    if chars.get(i) == Some(&'.') {
+        let (precision, next) = parse_number(&chars, i + 1)?;
+        result.precision = Some(precision.ok_or("missing precision")?);
+        i = next;
+    }
+
+    if i < chars.len() {
+        return Err(format!("invalid format spec `{}`", spec));
+    }
+
+    Ok(result)
+}
+
+/// Reads the digits from `start` on, if there are any, and returns where
+/// they end.
+fn parse_number(chars: &[char], start: usize) -> Result<(Option<usize>, usize), String> {
+    let end = chars[start..]
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .map_or(chars.len(), |length| start + length);
+
+    if start == end {
+        return Ok((None, end));
+    }
+
+    let digits: String = chars[start..end].iter().collect();
+
+    match digits.parse() {
+        Ok(number) if number <= MAX_WIDTH => Ok((Some(number), end)),
+        _ => Err(format!("`{}` is larger than {}", digits, MAX_WIDTH)),
+    }
+}
+
+impl<'de> Deserialize<'de> for Format {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let format = String::deserialize(deserializer)?;
+
+        format
+            .parse()
+            .map_err(|e| de::Error::custom(format!("invalid format `{}`: {}", format, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: &str, values: &Values) -> String {
+        format
+            .parse::<Format>()
+            .unwrap()
+            .render(values)
+            .as_str()
+            .to_owned()
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        let values = Values::new().with("title", "Song").with("volume", 42i64);
+
+        assert_eq!(render("{title}: {volume}%", &values), "Song: 42%");
+    }
+
+    #[test]
+    fn pads_to_the_width() {
+        let values = Values::new().with("text", "ab").with("number", 7i64);
+
+        assert_eq!(render("{text:4}|", &values), "ab  |");
+        assert_eq!(render("{number:4}|", &values), "   7|");
+        assert_eq!(render("{text:>4}|", &values), "  ab|");
+        assert_eq!(render("{text:*^6}|", &values), "**ab**|");
+        assert_eq!(render("{number:03}", &values), "007");
+        assert_eq!(render("{text:1}", &values), "ab");
+    }
+
+    #[test]
+    fn applies_the_precision() {
+        let values = Values::new()
+            .with("text", "abcdef")
+            .with("number", 1.0f64 / 3.0);
+
+        assert_eq!(render("{text:.3}", &values), "abc");
+        assert_eq!(render("{number:.2}", &values), "0.33");
+        assert_eq!(render("{number:6.1}", &values), "   0.3");
+    }
+
+    #[test]
+    fn drops_sections_with_missing_values() {
+        let values = Values::new().with("artist", "A").with("title", "T");
+
+        assert_eq!(render("[{artist} - ]{title}", &values), "A - T");
+        assert_eq!(render("[{album} - ]{title}", &values), "T");
+        assert_eq!(render("[{artist}[ ({album})]]", &values), "A");
+    }
+
+    #[test]
+    fn leaves_out_missing_values_outside_of_sections() {
+        assert_eq!(render("<{title}>", &Values::new()), "<>");
+    }
+
+    #[test]
+    fn escapes_values_but_not_the_template() {
+        let values = Values::new().with("title", "<b>Tom & Jerry</b>");
+
+        assert_eq!(
+            render("<i>{title}</i>", &values),
+            "<i>&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;</i>"
+        );
+    }
+
+    #[test]
+    fn pads_by_the_visible_length() {
+        let values = Values::new()
+            .with("title", "a&b")
+            .with("icon", Markup::trusted("<b>x</b>".into()));
+
+        assert_eq!(render("{title:5}|", &values), "a&amp;b  |");
+        assert_eq!(render("{icon:3}|", &values), "<b>x</b>  |");
+        assert_eq!(render("{icon:&>3}", &values), "&amp;&amp;<b>x</b>");
+    }
+
+    #[test]
+    fn makes_escaped_characters_literal() {
+        let values = Values::new().with("title", "T");
+
+        assert_eq!(render("\\[{title}\\] \\{x\\}", &values), "[T] {x}");
+    }
+
+    #[test]
+    fn rejects_invalid_formats() {
+        for format in &[
+            "{title",
+            "title}",
+            "[{title}",
+            "{title}]",
+            "{}",
+            "{ti-tle}",
+            "{title:x}",
+            "{title:5.}",
+            "{title:1001}",
+            "{title:.1001}",
+            "{title:99999999999999999999999}",
+            "trailing \\",
+        ] {
+            assert!(format.parse::<Format>().is_err(), "{} parsed", format);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::block::Icon;
+use crate::error::Error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+pub const DEFAULT_ICON_SET: &str = "awesome5";
+
+lazy_static! {
+    static ref ACTIVE_ICONS: RwLock<IconSet> =
+        RwLock::new(IconSet::builtin(DEFAULT_ICON_SET).unwrap());
+}
+
+/// Resolves icons to the glyphs of one icon font, or to plain text.
+#[derive(Clone)]
+pub struct IconSet {
+    glyphs: fn(Icon) -> &'static str,
+    overrides: HashMap<Icon, String>,
+}
+
+impl IconSet {
+    pub fn builtin(name: &str) -> Option<IconSet> {
+        let glyphs = match name {
+            "awesome4" => awesome4,
+            "awesome5" => awesome5,
+            "nerd-font" => nerd_font,
+            "material" => material,
+            "emoji" => emoji,
+            "text" => text,
+            _ => return None,
+        };
+
+        Some(IconSet {
+            glyphs,
+            overrides: HashMap::new(),
+        })
+    }
+
+    /// The `[icons]` section: a built-in set, and glyphs replacing some of
+    /// its icons.
+    pub fn configure(config: &IconsConfig) -> Result<IconSet, Error> {
+        let name = config.set.as_deref().unwrap_or(DEFAULT_ICON_SET);
+        let mut icons = IconSet::builtin(name).ok_or_else(|| {
+            Error::config_key(
+                "set",
+                format!(
+                    "unknown icon set `{}`, expected one of awesome4, awesome5, nerd-font, \
+                     material, emoji or text",
+                    name
+                ),
+            )
+        })?;
+
+        icons.overrides = config.overrides.clone();
+
+        Ok(icons)
+    }
+
+    pub fn get(&self, icon: Icon) -> &str {
+        match self.overrides.get(&icon) {
+            Some(glyph) => glyph,
+            None => (self.glyphs)(icon),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconsConfig {
+    set: Option<String>,
+    /// Glyphs by icon name, e.g. `music = "♪"`.
+    overrides: HashMap<Icon, String>,
+}
+
+/// Switches the icons every block is rendered with from now on.
+pub fn set_active_icons(icons: IconSet) {
+    *ACTIVE_ICONS.write().unwrap() = icons;
+}
+
+pub fn glyph(icon: Icon) -> String {
+    ACTIVE_ICONS.read().unwrap().get(icon).to_owned()
+}
+
+fn awesome4(icon: Icon) -> &'static str {
+    match icon {
+        Icon::LightningBolt => "\u{f0e7}",
+        Icon::VolumeUp => "\u{f028}",
+        Icon::VolumeDown => "\u{f027}",
+        // Font Awesome 4 has no crossed out speaker.
+        Icon::VolumeOff | Icon::VolumeMute => "\u{f026}",
+        Icon::Headphones => "\u{f025}",
+        Icon::Tv => "\u{f26c}",
+        Icon::Microphone => "\u{f130}",
+        Icon::MicrophoneOff => "\u{f131}",
+        Icon::Globe => "\u{f0ac}",
+        Icon::Music => "\u{f001}",
+        Icon::Play => "\u{f04b}",
+        Icon::Pause => "\u{f04c}",
+        Icon::HDD => "\u{f0a0}",
+        Icon::Calendar => "\u{f073}",
+    }
+}
+
+fn awesome5(icon: Icon) -> &'static str {
+    match icon {
+        Icon::LightningBolt => "\u{f0e7}",
+        Icon::VolumeUp => "\u{f028}",
+        Icon::VolumeDown => "\u{f027}",
+        Icon::VolumeOff => "\u{f026}",
+        Icon::VolumeMute => "\u{f6a9}",
+        Icon::Headphones => "\u{f025}",
+        Icon::Tv => "\u{f26c}",
+        Icon::Microphone => "\u{f130}",
+        Icon::MicrophoneOff => "\u{f131}",
+        Icon::Globe => "\u{f0ac}",
+        Icon::Music => "\u{f001}",
+        Icon::Play => "\u{f04b}",
+        Icon::Pause => "\u{f04c}",
+        Icon::HDD => "\u{f0a0}",
+        Icon::Calendar => "\u{f133}",
+    }
+}
+
+/// The Material Design range of Nerd Fonts 3.
+fn nerd_font(icon: Icon) -> &'static str {
+    match icon {
+        Icon::LightningBolt => "\u{f0241}",
+        Icon::VolumeUp => "\u{f057e}",
+        Icon::VolumeDown => "\u{f0580}",
+        Icon::VolumeOff => "\u{f0581}",
+        Icon::VolumeMute => "\u{f075f}",
+        Icon::Headphones => "\u{f02cb}",
+        Icon::Tv => "\u{f0502}",
+        Icon::Microphone => "\u{f036c}",
+        Icon::MicrophoneOff => "\u{f036d}",
+        Icon::Globe => "\u{f059f}",
+        Icon::Music => "\u{f075a}",
+        Icon::Play => "\u{f040a}",
+        Icon::Pause => "\u{f03e4}",
+        Icon::HDD => "\u{f02ca}",
+        Icon::Calendar => "\u{f00ed}",
+    }
+}
+
+/// Google's Material Icons font.
+fn material(icon: Icon) -> &'static str {
+    match icon {
+        Icon::LightningBolt => "\u{e3e7}",
+        Icon::VolumeUp => "\u{e050}",
+        Icon::VolumeDown => "\u{e04d}",
+        Icon::VolumeOff => "\u{e04f}",
+        Icon::VolumeMute => "\u{e04e}",
+        Icon::Headphones => "\u{e310}",
+        Icon::Tv => "\u{e333}",
+        Icon::Microphone => "\u{e029}",
+        Icon::MicrophoneOff => "\u{e02b}",
+        Icon::Globe => "\u{e80b}",
+        Icon::Music => "\u{e405}",
+        Icon::Play => "\u{e037}",
+        Icon::Pause => "\u{e034}",
+        Icon::HDD => "\u{e1db}",
+        Icon::Calendar => "\u{e935}",
+    }
+}
+
+fn emoji(icon: Icon) -> &'static str {
+    match icon {
+        Icon::LightningBolt => "\u{26a1}",
+        Icon::VolumeUp => "\u{1f50a}",
+        Icon::VolumeDown => "\u{1f509}",
+        Icon::VolumeOff => "\u{1f508}",
+        Icon::VolumeMute => "\u{1f507}",
+        Icon::Headphones => "\u{1f3a7}",
+        Icon::Tv => "\u{1f4fa}",
+        Icon::Microphone => "\u{1f3a4}",
+        Icon::MicrophoneOff => "\u{1f507}",
+        Icon::Globe => "\u{1f310}",
+        Icon::Music => "\u{1f3b5}",
+        Icon::Play => "\u{25b6}",
+        Icon::Pause => "\u{23f8}",
+        Icon::HDD => "\u{1f4be}",
+        Icon::Calendar => "\u{1f4c5}",
+    }
+}
+
+/// For fonts without any symbols.
+fn text(icon: Icon) -> &'static str {
+    match icon {
+        Icon::LightningBolt => "load",
+        Icon::VolumeUp | Icon::VolumeDown | Icon::VolumeOff => "vol",
+        Icon::VolumeMute => "mute",
+        Icon::Headphones => "phones",
+        Icon::Tv => "tv",
+        Icon::Microphone => "mic",
+        Icon::MicrophoneOff => "mic off",
+        Icon::Globe => "net",
+        Icon::Music => "music",
+        Icon::Play => ">",
+        Icon::Pause => "||",
+        Icon::HDD => "disk",
+        Icon::Calendar => "date",
+    }
+}
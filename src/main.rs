@@ -1,19 +1,34 @@
-use crate::block::Block;
+use crate::block::{BlockError, Layout, Notifier};
+use crate::blocks::command::CommandBlock;
 use crate::blocks::date_time::DateTime;
+use crate::blocks::error_block::ErrorBlock;
 use crate::blocks::free_disk_space::FreeDiskSpace;
 use crate::blocks::media_player::MediaPlayer;
 use crate::blocks::network_interface::NetworkInterface;
 use crate::blocks::system_load::SystemLoad;
 use crate::blocks::volume::VolumeFactory;
-use crate::i3bar::{get_header_json, read_event, sources_to_json};
-use log::LevelFilter;
+use crate::cli::{Command, Options, USAGE};
+use crate::click::{ClickBindings, REFRESH_ACTION};
+use crate::config::{
+    get_str, parse_block_config, parse_config, parse_interval, read_config, BlockFactory,
+};
+use crate::control::{create_control_socket, query_reply, ControlRequest, Reply};
+use crate::error::Error;
+use crate::icons::{set_active_icons, IconSet};
+use crate::output::{create_output, ClickEvents, Output, DEFAULT_OUTPUT};
+use crate::outputs::i3bar::I3Bar;
+use crate::scheduler::{Message, ScheduledBlock, Scheduler};
+use crate::signals::create_signal_thread;
+use crate::theme::Theme;
+use crate::watcher::create_config_watcher;
 use simplelog::{Config, WriteLogger};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
-use std::io::Read;
+use std::path::Path;
 use std::rc::Rc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::time::Duration;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use toml::Value;
 
 #[macro_use]
@@ -23,135 +38,515 @@ extern crate log;
 
 mod block;
 mod blocks;
-mod i3bar;
+mod cli;
+mod click;
+mod config;
+mod control;
+mod error;
+mod format;
+mod icons;
+mod markup;
+mod output;
+mod outputs;
+mod scheduler;
+mod signals;
+mod socket_path;
+mod theme;
+mod watcher;
 
 fn main() {
-    WriteLogger::init(
-        LevelFilter::Debug,
-        Config::default(),
-        File::create("stsbr.log").unwrap(),
-    )
-    .unwrap();
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("stsbr {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(e) => {
+            eprint!("stsbr: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = init_logging(&options) {
+        eprintln!("stsbr: {}", e);
+    }
+
+    if options.check_config {
+        let (errors, warnings) = check_config(&options);
+
+        for e in warnings {
+            eprintln!("{}: warning: {}", options.config.display(), e);
+        }
+
+        for e in errors.iter() {
+            eprintln!("{}: {}", options.config.display(), e);
+        }
+
+        if !errors.is_empty() {
+            std::process::exit(1);
+        }
+
+        println!("{}: configuration is valid", options.config.display());
+        return;
+    }
+
+    let (sender, receiver): (Sender<Message>, Receiver<Message>) = std::sync::mpsc::channel();
 
-    let mut sources = load_blocks();
-    let receiver = create_stdin_thread();
+    if let Err(e) = create_signal_thread(sender.clone()) {
+        error!("Cannot handle signals: {}", e);
+    }
+
+    if let Err(e) = create_config_watcher(&options.config, sender.clone()) {
+        warn!("Cannot watch {}: {}", options.config.display(), e);
+    }
+
+    match &options.socket {
+        Some(socket) => {
+            if let Err(e) = create_control_socket(socket, sender.clone()) {
+                warn!("Cannot listen on {}: {}", socket.display(), e);
+            }
+        }
+        None => warn!("XDG_RUNTIME_DIR is not set, pass --socket to use stsbr-msg"),
+    }
+
+    let config = read_config(&options.config);
+    // The output is fixed for the whole run, a reload cannot switch protocols.
+    let output = create_configured_output(&options, &config);
+    let block_factories = create_block_factories();
+    let mut scheduler = Scheduler::new(load_blocks(&config, &block_factories, &sender));
+
+    if let Some(click_events) = output.click_events() {
+        create_stdin_thread(sender.clone(), click_events);
+    }
 
-    println!("{}", get_header_json(true));
-    println!("[");
+    if let Some(header) = output.header() {
+        println!("{}", header);
+    }
 
     loop {
-        println!("{},", sources_to_json(&mut sources));
+        if scheduler.update() {
+            println!("{}", output.status_line(&scheduler.rendered_blocks()));
+        }
 
-        while let Ok(x) = receiver.try_recv() {
-            if x != "[\n" {
-                let event = read_event(x.trim_matches(','));
-                let block = sources.get(event.instance()).unwrap();
+        let message = match scheduler.time_until_next_update() {
+            Some(timeout) => receiver.recv_timeout(timeout),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
 
-                block.handle_click(event);
+        match message {
+            Ok(Message::Click(event)) => scheduler.handle_click(event),
+            Ok(Message::Update(block)) => scheduler.invalidate(&block),
+            Ok(Message::Stop) => scheduler.pause(),
+            Ok(Message::Continue) => scheduler.resume(),
+            Ok(Message::Reload) => {
+                match reload_blocks(&options.config, &block_factories, &sender) {
+                    Ok(blocks) => {
+                        info!("Reloaded {}", options.config.display());
+                        scheduler.replace_blocks(blocks);
+                    }
+                    Err(errors) => {
+                        for e in errors {
+                            error!("Keeping the previous configuration: {}", e);
+                        }
+                    }
+                }
             }
+            Ok(Message::Control(request, reply)) => {
+                let _ = reply.send(handle_control_request(&mut scheduler, request));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
         }
+    }
+}
 
-        std::thread::sleep(Duration::from_millis(200));
+fn handle_control_request(scheduler: &mut Scheduler, request: ControlRequest) -> Reply {
+    match request {
+        ControlRequest::Refresh(None) => scheduler.invalidate_all(),
+        ControlRequest::Refresh(Some(block)) => scheduler.run_action(&block, REFRESH_ACTION)?,
+        ControlRequest::Action { block, action } => scheduler.run_action(&block, &action)?,
+        ControlRequest::Query => return Ok(query_reply(&scheduler.rendered_blocks())),
     }
+
+    Ok("ok".into())
+}
+
+fn init_logging(options: &Options) -> Result<(), String> {
+    let result = match &options.log_file {
+        Some(path) => {
+            let file = create_log_file(path)
+                .map_err(|e| format!("Cannot create log file {}: {}", path.display(), e))?;
+
+            WriteLogger::init(options.log_level, Config::default(), file)
+        }
+        None => WriteLogger::init(options.log_level, Config::default(), std::io::stderr()),
+    };
+
+    result.map_err(|e| e.to_string())
 }
 
-fn load_blocks() -> Vec<Box<Block>> {
-    let mut config = String::new();
+fn create_log_file(path: &Path) -> std::io::Result<File> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
 
-    File::open(".stsbr.toml")
-        .unwrap()
-        .read_to_string(&mut config)
-        .unwrap();
+    File::create(path)
+}
 
+/// Validates the configuration without starting any block.
+/// Returns the errors and the warnings found.
+fn check_config(options: &Options) -> (Vec<Error>, Vec<Error>) {
+    let config = match read_config(&options.config) {
+        Ok(config) => config,
+        Err(e) => return (vec![e], vec![]),
+    };
     let block_factories = create_block_factories();
-    let mut sources: Vec<Box<dyn Block>> = vec![];
+    let mut errors = vec![];
+    let mut warnings = vec![];
+
+    if let Err(e) = configured_output_name(options, &config).and_then(create_output) {
+        errors.push(e);
+    }
+
+    let theme = create_theme(&config).unwrap_or_else(|e| {
+        errors.push(e);
+        Theme::default()
+    });
+
+    if let Err(e) = create_icons(&config) {
+        errors.push(e);
+    }
 
-    parse_config(
+    let result = parse_config(
         &config,
-        Box::new(|section| {
-            let module_name = section["module"].as_str().unwrap();
+        Box::new(|name, section| {
+            let checked = find_factory(&block_factories, section).and_then(|factory| {
+                warnings.extend(unknown_key_warnings(factory, name, section));
+
+                factory.check(section)
+            });
 
-            sources.push(block_factories[module_name](section));
+            let checked = checked
+                .and_then(|_| create_block_theme(&theme, section))
+                .and_then(|_| parse_block_config::<Layout>(section))
+                .and_then(|_| ClickBindings::from_section(section));
+
+            if let Err(e) = checked {
+                errors.push(e.in_section(name));
+            }
+
+            if let Some(Err(e)) = section.get("interval").map(parse_interval) {
+                errors.push(e.in_section(name));
+            }
         }),
     );
+
+    if let Err(e) = result {
+        errors.push(e);
+    }
+
+    (errors, warnings)
+}
+
+fn unknown_key_warnings(factory: &BlockFactory, name: &str, section: &Value) -> Vec<Error> {
+    factory
+        .unknown_keys(section)
+        .iter()
+        .map(|key| Error::config_key(key, "unknown key, ignoring it".into()).in_section(name))
+        .collect()
+}
+
+/// The output given on the command line wins over the one in the config.
+fn configured_output_name<'a>(options: &'a Options, config: &'a Value) -> Result<&'a str, Error> {
+    match &options.output {
+        Some(output) => Ok(output),
+        None if config.get("output").is_some() => get_str(config, "output"),
+        None => Ok(DEFAULT_OUTPUT),
+    }
+}
+
+fn create_configured_output(options: &Options, config: &Result<Value, Error>) -> Box<dyn Output> {
+    let name = match config {
+        Ok(config) => configured_output_name(options, config),
+        Err(_) => Ok(options.output.as_deref().unwrap_or(DEFAULT_OUTPUT)),
+    };
+
+    name.and_then(create_output).unwrap_or_else(|e| {
+        error!("{}, falling back to {}", e, DEFAULT_OUTPUT);
+        Box::new(I3Bar::new())
+    })
+}
+
+fn load_blocks(
+    config: &Result<Value, Error>,
+    block_factories: &BlockFactories,
+    sender: &Sender<Message>,
+) -> Vec<ScheduledBlock> {
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            return vec![create_error_block("error", e)];
+        }
+    };
+
+    match create_icons(config) {
+        Ok(icons) => set_active_icons(icons),
+        Err(e) => error!("{}", e),
+    }
+
+    let (sources, errors) = create_blocks(config, block_factories, sender);
+
+    for e in errors {
+        error!("{}", e);
+    }
+
     sources
 }
 
-fn parse_config<'a>(config: &str, mut on_section: Box<'a + FnMut(&Value)>) {
-    let parsed_config = config.parse::<Value>().unwrap();
+/// Builds the blocks for a changed configuration. Unlike at startup, an
+/// invalid configuration is rejected as a whole, so the bar keeps running
+/// with the blocks it has.
+fn reload_blocks(
+    path: &Path,
+    block_factories: &BlockFactories,
+    sender: &Sender<Message>,
+) -> Result<Vec<ScheduledBlock>, Vec<Error>> {
+    let config = read_config(path).map_err(|e| vec![e])?;
+    let icons = create_icons(&config);
+    let (sources, mut errors) = create_blocks(&config, block_factories, sender);
+
+    match icons {
+        Ok(icons) if errors.is_empty() => {
+            set_active_icons(icons);
+            Ok(sources)
+        }
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.push(e);
+            Err(errors)
+        }
+    }
+}
+
+/// Creates the configured blocks. Sections that fail are replaced by error
+/// blocks, and their errors are returned as well.
+fn create_blocks(
+    config: &Value,
+    block_factories: &BlockFactories,
+    sender: &Sender<Message>,
+) -> (Vec<ScheduledBlock>, Vec<Error>) {
+    let mut sources: Vec<ScheduledBlock> = vec![];
+    let mut errors = vec![];
+    let theme = create_theme(config).unwrap_or_else(|e| {
+        errors.push(e);
+        Theme::default()
+    });
+
+    let result = parse_config(
+        config,
+        Box::new(|name, section| {
+            let notifier = Notifier::new(sender.clone(), name.to_owned());
+            let block = find_factory(block_factories, section)
+                .and_then(|factory| {
+                    for warning in unknown_key_warnings(factory, name, section) {
+                        warn!("{}", warning);
+                    }
 
-    if let Value::Table(i) = parsed_config {
-        if let Value::Table(toml_sources) = i.get("sources").unwrap() {
-            for (_, section) in toml_sources.iter().rev() {
-                on_section(&section);
+                    let theme = create_block_theme(&theme, section)?;
+                    let layout = parse_block_config(section)?;
+                    let bindings = ClickBindings::from_section(section)?;
+                    let block = factory.create(section, notifier)?;
+                    bindings.check_actions(block.actions())?;
+
+                    Ok((block, theme, layout, bindings))
+                })
+                .map_err(|e| e.in_section(name));
+            let interval = section.get("interval").and_then(|value| {
+                parse_interval(value)
+                    .map_err(|e| warn!("{}, using the block's default", e.in_section(name)))
+                    .ok()
+            });
+
+            match block {
+                Ok((block, theme, layout, bindings)) => {
+                    // The factory succeeded, so `module` is a valid string.
+                    let module = get_str(section, "module").unwrap_or_default();
+
+                    sources.push(
+                        ScheduledBlock::new(name.to_owned(), module.to_owned(), block, interval)
+                            .with_theme(theme)
+                            .with_layout(layout)
+                            .with_bindings(bindings),
+                    );
+                }
+                Err(e) => {
+                    sources.push(create_error_block(name, &e));
+                    errors.push(e);
+                }
             }
+        }),
+    );
+
+    if let Err(e) = result {
+        sources.push(create_error_block("error", &e));
+        errors.push(e);
+    }
+
+    (sources, errors)
+}
+
+/// The `[theme]` section, or the default theme if there is none.
+fn create_theme(config: &Value) -> Result<Theme, Error> {
+    match config.get("theme") {
+        Some(section) => {
+            parse_block_config(section).and_then(|theme| Theme::default().configure(&theme))
+        }
+        None => Ok(Theme::default()),
+    }
+    .map_err(|e| e.in_section("theme"))
+}
+
+/// The `[icons]` section, or the default icon set if there is none.
+fn create_icons(config: &Value) -> Result<IconSet, Error> {
+    match config.get("icons") {
+        Some(section) => parse_block_config(section).and_then(|icons| IconSet::configure(&icons)),
+        None => IconSet::configure(&Default::default()),
+    }
+    .map_err(|e| e.in_section("icons"))
+}
+
+/// A block's own `theme` table overrides the bar's theme.
+fn create_block_theme(theme: &Theme, section: &Value) -> Result<Theme, Error> {
+    match section.get("theme") {
+        Some(overrides) => {
+            parse_block_config(overrides).and_then(|config| theme.configure(&config))
         }
+        None => Ok(theme.clone()),
     }
 }
 
-type BlockFactory = Fn(&Value) -> Box<Block>;
-fn create_block_factories() -> HashMap<String, Box<BlockFactory>> {
-    let volume_factory = Rc::new(VolumeFactory::new());
+fn create_error_block(name: &str, error: &Error) -> ScheduledBlock {
+    ScheduledBlock::new(
+        name.to_owned(),
+        "error".to_owned(),
+        Box::new(ErrorBlock::new(error)),
+        None,
+    )
+}
+
+fn find_factory<'a>(
+    block_factories: &'a BlockFactories,
+    section: &Value,
+) -> Result<&'a BlockFactory, Error> {
+    let module_name = get_str(section, "module")?;
+
+    block_factories
+        .get(module_name)
+        .ok_or_else(|| Error::config_key("module", format!("unknown module `{}`", module_name)))
+}
+
+type BlockFactories = HashMap<String, BlockFactory>;
+
+/// The factories are kept for the whole run, so resources they share between
+/// blocks, like the PulseAudio connection, survive configuration reloads.
+fn create_block_factories() -> BlockFactories {
+    // Connecting to PulseAudio is deferred until a block needs it.
+    let volume_factory: Rc<RefCell<Option<VolumeFactory>>> = Rc::new(RefCell::new(None));
 
-    let mut block_factories: HashMap<String, Box<BlockFactory>> = HashMap::new();
+    let mut block_factories: BlockFactories = HashMap::new();
 
-    block_factories.insert("date_time".into(), Box::new(|_| Box::new(DateTime::new())));
+    block_factories.insert("applications".into(), {
+        let volume_factory = volume_factory.clone();
+
+        BlockFactory::new(move |config, notifier| {
+            let applications = with_volume_factory(&volume_factory, |factory| {
+                factory.new_applications(config, notifier)
+            })?;
+
+            Ok(Box::new(applications))
+        })
+    });
+    block_factories.insert(
+        "command".into(),
+        BlockFactory::new(|config, notifier| Ok(Box::new(CommandBlock::new(config, notifier)?))),
+    );
+    block_factories.insert(
+        "date_time".into(),
+        BlockFactory::new(|config, _| Ok(Box::new(DateTime::new(config)))),
+    );
     block_factories.insert(
         "free_disk_space".into(),
-        Box::new(|_| Box::new(FreeDiskSpace::new())),
+        BlockFactory::new(|config, _| Ok(Box::new(FreeDiskSpace::new(config)))),
     );
     block_factories.insert(
         "media_player".into(),
-        Box::new(|_| Box::new(MediaPlayer::new())),
+        BlockFactory::new(|config, notifier| Ok(Box::new(MediaPlayer::new(config, notifier)))),
     );
+    block_factories.insert("microphone".into(), {
+        let volume_factory = volume_factory.clone();
+
+        BlockFactory::new(move |config, notifier| {
+            let microphone = with_volume_factory(&volume_factory, |factory| {
+                factory.new_microphone(config, notifier)
+            })?;
+
+            Ok(Box::new(microphone))
+        })
+    });
     block_factories.insert(
         "network_interface".into(),
-        Box::new(|section| {
-            Box::new(NetworkInterface::new(
-                section["interface"].as_str().unwrap().into(),
-            ))
-        }),
+        BlockFactory::new(|config, _| Ok(Box::new(NetworkInterface::new(config)))),
     );
     block_factories.insert(
         "system_load".into(),
-        Box::new(|_| Box::new(SystemLoad::new())),
+        BlockFactory::new(|config, _| Ok(Box::new(SystemLoad::new(config)))),
     );
     block_factories.insert(
         "volume".into(),
-        Box::new(move |section| {
-            if let Value::Table(x) = section {
-                let volume = volume_factory
-                    .clone()
-                    .new_volume(x["sink"].as_str().unwrap().into());
-
-                Box::new(volume)
-            } else {
-                panic!("Invalid configuration file");
-            }
+        BlockFactory::new(move |config, notifier| {
+            let volume = with_volume_factory(&volume_factory, |factory| {
+                factory.new_volume(config, notifier)
+            })?;
+
+            Ok(Box::new(volume))
         }),
     );
 
     block_factories
 }
 
-fn create_stdin_thread() -> Receiver<String> {
-    let (sender, receiver): (Sender<String>, Receiver<String>) = std::sync::mpsc::channel();
+/// Connects to PulseAudio for the first block that needs it. A failed
+/// connection is retried by the next block, e.g. after a reload, since
+/// PulseAudio often starts after the bar.
+fn with_volume_factory<T>(
+    volume_factory: &RefCell<Option<VolumeFactory>>,
+    create: impl FnOnce(&VolumeFactory) -> T,
+) -> Result<T, BlockError> {
+    let mut volume_factory = volume_factory.borrow_mut();
 
-    std::thread::spawn(move || {
-        let stdin = std::io::stdin();
+    if volume_factory.is_none() {
+        *volume_factory = Some(VolumeFactory::new()?);
+    }
 
-        loop {
-            let mut line = String::new();
+    Ok(create(volume_factory.as_ref().unwrap()))
+}
 
-            match stdin.read_line(&mut line) {
-                Ok(_) => sender.send(line).unwrap(),
-                Err(e) => {
-                    error!("{}", e);
-                    break;
+fn create_stdin_thread(sender: Sender<Message>, click_events: ClickEvents) {
+    std::thread::spawn(move || {
+        for event in click_events {
+            match event {
+                Ok(event) => {
+                    if sender.send(Message::Click(event)).is_err() {
+                        break;
+                    }
                 }
-            };
+                Err(e) => warn!("Ignoring click event: {}", e),
+            }
         }
     });
-
-    receiver
 }
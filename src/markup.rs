@@ -0,0 +1,154 @@
+/// Pango markup that is safe to hand to the bar: text from outside, like song
+/// titles, only gets in escaped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Markup(String);
+
+impl Markup {
+    pub fn new() -> Self {
+        Markup(String::new())
+    }
+
+    /// Plain text, shown exactly as it is.
+    pub fn text(text: &str) -> Self {
+        Markup(escape(text))
+    }
+
+    /// Markup written by the user, like the literal parts of format strings.
+    /// Never use this for text coming from anywhere else.
+    pub fn trusted(markup: String) -> Self {
+        Markup(markup)
+    }
+
+    pub fn push(mut self, markup: &Markup) -> Self {
+        self.0.push_str(&markup.0);
+        self
+    }
+
+    pub fn push_text(mut self, text: &str) -> Self {
+        self.0.push_str(&escape(text));
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The text without any markup, for bars that don't understand Pango.
+    pub fn to_plain_text(&self) -> String {
+        strip(&self.0)
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Drops the tags of Pango markup and resolves its entities.
+pub fn strip(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut rest = markup;
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' => match rest.find('>') {
+                Some(end) => rest = &rest[end + 1..],
+                None => break,
+            },
+            '&' => {
+                let entity = rest.find(';').and_then(|end| {
+                    let character = match &rest[1..end] {
+                        "amp" => Some('&'),
+                        "lt" => Some('<'),
+                        "gt" => Some('>'),
+                        "quot" => Some('"'),
+                        "apos" => Some('\''),
+                        number if number.starts_with("#x") => u32::from_str_radix(&number[2..], 16)
+                            .ok()
+                            .and_then(std::char::from_u32),
+                        number if number.starts_with('#') => {
+                            number[1..].parse().ok().and_then(std::char::from_u32)
+                        }
+                        _ => None,
+                    };
+
+                    character.map(|character| (character, end))
+                });
+
+                match entity {
+                    Some((character, end)) => {
+                        text.push(character);
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        text.push('&');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            c => {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(escape("plain ümlaut"), "plain ümlaut");
+    }
+
+    #[test]
+    fn strips_tags_and_resolves_entities() {
+        assert_eq!(
+            strip("<span foreground=\"#f00\"><b>a</b> &lt;&amp;&gt; b</span>"),
+            "a <&> b"
+        );
+        assert_eq!(strip("&quot;&apos;&#39;&#x41;&#228;"), "\"''Aä");
+    }
+
+    #[test]
+    fn keeps_unknown_entities_and_bare_ampersands() {
+        assert_eq!(strip("&nbsp; & &#xzz; &"), "&nbsp; & &#xzz; &");
+    }
+
+    #[test]
+    fn strip_undoes_escape() {
+        for text in &["", "a & b", "<<>>", "\"quoted\" 'text'", "&amp;", "x;&y"] {
+            assert_eq!(strip(&escape(text)), *text);
+        }
+    }
+
+    #[test]
+    fn builds_markup() {
+        let markup = Markup::text("a<b")
+            .push_text(" & ")
+            .push(&Markup::trusted("<b>c</b>".into()));
+
+        assert_eq!(markup.as_str(), "a&lt;b &amp; <b>c</b>");
+        assert_eq!(markup.to_plain_text(), "a<b & c");
+    }
+}
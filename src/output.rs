@@ -0,0 +1,70 @@
+use crate::block::ClickEvent;
+use crate::error::Error;
+use crate::markup::strip;
+use crate::outputs::i3bar::I3Bar;
+use crate::outputs::lemonbar::Lemonbar;
+use crate::outputs::plain::Plain;
+use crate::outputs::tmux::Tmux;
+use crate::scheduler::RenderedBlock;
+
+pub type ClickEvents = Box<dyn Iterator<Item = Result<ClickEvent, Error>> + Send>;
+
+/// A status bar protocol the block states are written in.
+pub trait Output {
+    /// Printed once before the first status line.
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// Renders one full status line from the blocks that have something to show.
+    fn status_line(&self, blocks: &[RenderedBlock]) -> String;
+
+    /// Parses the click events the bar sends to our stdin, if it sends any.
+    fn click_events(&self) -> Option<ClickEvents> {
+        None
+    }
+}
+
+/// Joins the rendered blocks with the separators of their themes, or with
+/// `default_separator` where the theme has none. Meant for outputs without
+/// Pango support, so markup in the separators is dropped, and what is left
+/// goes through `escape` like the text of the blocks.
+pub fn join_blocks(
+    blocks: &[RenderedBlock],
+    texts: &[String],
+    default_separator: &str,
+    escape: fn(&str) -> String,
+) -> String {
+    let mut line = String::new();
+
+    for (i, text) in texts.iter().enumerate() {
+        if i > 0 {
+            match blocks[i - 1].separator {
+                Some(separator) => line.push_str(&escape(&strip(separator))),
+                None => line.push_str(default_separator),
+            }
+        }
+
+        line.push_str(text);
+    }
+
+    line
+}
+
+pub const DEFAULT_OUTPUT: &str = "i3bar";
+
+pub fn create_output(name: &str) -> Result<Box<dyn Output>, Error> {
+    match name {
+        "i3bar" | "swaybar" => Ok(Box::new(I3Bar::new())),
+        "lemonbar" => Ok(Box::new(Lemonbar::new())),
+        "tmux" => Ok(Box::new(Tmux::new())),
+        "plain" => Ok(Box::new(Plain::new())),
+        _ => Err(Error::config_key(
+            "output",
+            format!(
+                "unknown output `{}`, expected one of i3bar, swaybar, lemonbar, tmux or plain",
+                name
+            ),
+        )),
+    }
+}
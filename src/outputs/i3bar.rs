@@ -0,0 +1,441 @@
+use crate::block::{Align, ClickEvent, Dimensions, MinWidth, Modifier, MouseButton, Position};
+use crate::error::Error;
+use crate::output::{ClickEvents, Output};
+use crate::scheduler::RenderedBlock;
+use crate::signals::{CONT_SIGNAL, STOP_SIGNAL};
+use serde::{Deserialize, Serialize};
+use std::borrow::ToOwned;
+use std::io::{BufReader, Bytes, Read};
+use std::string::ToString;
+
+#[derive(Serialize)]
+struct Header {
+    version: u64,
+    stop_signal: i32,
+    cont_signal: i32,
+    click_events: bool,
+}
+
+#[derive(Serialize)]
+struct BarBlock {
+    full_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_width: Option<MinWidth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    align: Option<Align>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    urgent: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separator: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separator_block_width: Option<u32>,
+    markup: String,
+    instance: String,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    name: Option<String>,
+    instance: Option<String>,
+    button: u32,
+    #[serde(default)]
+    modifiers: Vec<String>,
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+    #[serde(default)]
+    relative_x: i32,
+    #[serde(default)]
+    relative_y: i32,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    scale: Option<f64>,
+}
+
+/// The JSON protocol spoken by i3bar and swaybar.
+pub struct I3Bar {}
+
+impl Output for I3Bar {
+    fn header(&self) -> Option<String> {
+        let header = Header {
+            version: 1,
+            stop_signal: STOP_SIGNAL as i32,
+            cont_signal: CONT_SIGNAL as i32,
+            click_events: true,
+        };
+
+        // The status lines that follow are elements of one infinite array.
+        Some(format!("{}\n[", serde_json::to_string(&header).unwrap()))
+    }
+
+    fn status_line(&self, blocks: &[RenderedBlock]) -> String {
+        let mut bar_blocks = vec![];
+
+        for (i, block) in blocks.iter().enumerate() {
+            let mut bar_block = convert_to_bar_block(block);
+
+            if let (Some(separator), true) = (block.separator, i + 1 < blocks.len()) {
+                bar_block.separator = Some(false);
+                bar_block.separator_block_width = Some(0);
+                bar_blocks.push(bar_block);
+                bar_blocks.push(separator_block(separator));
+            } else {
+                bar_blocks.push(bar_block);
+            }
+        }
+
+        format!("{},", serde_json::to_string(&bar_blocks).unwrap())
+    }
+
+    fn click_events(&self) -> Option<ClickEvents> {
+        Some(Box::new(EventReader::new(std::io::stdin())))
+    }
+}
+
+impl I3Bar {
+    pub fn new() -> Self {
+        I3Bar {}
+    }
+}
+
+/// Blocks are sent with their module as `name` and their own unique name as
+/// `instance`, which is what click events are routed by.
+fn convert_to_bar_block(block: &RenderedBlock) -> BarBlock {
+    let st = block.state;
+
+    BarBlock {
+        full_text: st.text().as_str().to_owned(),
+        short_text: st.short_text().map(|text| text.as_str().to_owned()),
+        color: st.color().cloned(),
+        background: st.background().cloned(),
+        border: st.border().cloned(),
+        min_width: st.min_width().cloned(),
+        align: st.align(),
+        urgent: st.urgent(),
+        name: Some(block.module.to_owned()),
+        separator: st.separator(),
+        separator_block_width: st.separator_block_width(),
+        markup: "pango".to_string(),
+        instance: block.name.to_owned(),
+    }
+}
+
+/// Draws a theme's separator glyph in place of i3bar's separator line.
+fn separator_block(separator: &str) -> BarBlock {
+    BarBlock {
+        full_text: separator.to_owned(),
+        short_text: None,
+        color: None,
+        background: None,
+        border: None,
+        min_width: None,
+        align: None,
+        urgent: None,
+        name: Some("separator".to_string()),
+        separator: Some(false),
+        separator_block_width: Some(0),
+        markup: "pango".to_string(),
+        instance: String::new(),
+    }
+}
+
+fn read_event(raw: &[u8]) -> Result<ClickEvent, Error> {
+    let raw_event = serde_json::from_slice::<Event>(raw).map_err(|e| {
+        Error::Protocol(format!(
+            "Invalid click event {:?}: {}",
+            String::from_utf8_lossy(raw),
+            e
+        ))
+    })?;
+
+    let button = MouseButton::from_code(raw_event.button)
+        .ok_or_else(|| Error::Protocol(format!("Unknown mouse button {}", raw_event.button)))?;
+
+    let instance = raw_event
+        .instance
+        .ok_or_else(|| Error::Protocol("Click event without an instance".into()))?;
+
+    let modifiers = raw_event
+        .modifiers
+        .iter()
+        .filter_map(|modifier| match modifier.as_str() {
+            "Shift" => Some(Modifier::Shift),
+            "Control" => Some(Modifier::Control),
+            "Lock" => Some(Modifier::Lock),
+            "Mod1" => Some(Modifier::Mod1),
+            "Mod2" => Some(Modifier::Mod2),
+            "Mod3" => Some(Modifier::Mod3),
+            "Mod4" => Some(Modifier::Mod4),
+            "Mod5" => Some(Modifier::Mod5),
+            _ => {
+                warn!("Unknown modifier {}", modifier);
+                None
+            }
+        })
+        .collect();
+
+    let mut event = ClickEvent::new(button, instance)
+        .with_modifiers(modifiers)
+        .with_position(Position(raw_event.x, raw_event.y))
+        .with_relative_position(Position(raw_event.relative_x, raw_event.relative_y))
+        .with_block_dimensions(Dimensions(raw_event.width, raw_event.height))
+        .with_scale(raw_event.scale.unwrap_or(1.0));
+
+    if let Some(name) = raw_event.name {
+        event = event.with_name(name);
+    }
+
+    Ok(event)
+}
+
+/// Real click events are a few hundred bytes, anything longer means the
+/// stream is broken.
+const MAX_EVENT_SIZE: usize = 64 * 1024;
+
+enum ReaderState {
+    BeforeArray,
+    BetweenEvents,
+    InEvent {
+        depth: usize,
+        in_string: bool,
+        escaped: bool,
+    },
+    /// Skipping the rest of the line after an event got too long. Strings
+    /// cannot contain raw newlines, so the next line starts outside of one.
+    Resync,
+    AfterArray,
+}
+
+/// Reads click events from the infinite JSON array i3bar and swaybar write to
+/// our stdin. Events are split out of the byte stream by tracking nesting, so
+/// the reader does not depend on how the array is broken into lines.
+pub struct EventReader<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    state: ReaderState,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(input: R) -> Self {
+        EventReader {
+            bytes: BufReader::new(input).bytes(),
+            state: ReaderState::BeforeArray,
+            buffer: vec![],
+        }
+    }
+
+    /// Feeds one byte to the state machine, returning a complete event once its
+    /// closing brace has been seen.
+    fn push(&mut self, byte: u8) -> Option<Result<Vec<u8>, Error>> {
+        match self.state {
+            ReaderState::BeforeArray => match byte {
+                b'[' => self.state = ReaderState::BetweenEvents,
+                _ if byte.is_ascii_whitespace() => {}
+                _ => warn!("Unexpected {:?} before the event array", byte as char),
+            },
+            ReaderState::BetweenEvents => match byte {
+                b'{' => {
+                    self.buffer.push(byte);
+                    self.state = ReaderState::InEvent {
+                        depth: 1,
+                        in_string: false,
+                        escaped: false,
+                    };
+                }
+                b']' => self.state = ReaderState::AfterArray,
+                b',' => {}
+                _ if byte.is_ascii_whitespace() => {}
+                _ => warn!("Unexpected {:?} between events", byte as char),
+            },
+            ReaderState::InEvent {
+                ref mut depth,
+                ref mut in_string,
+                ref mut escaped,
+            } => {
+                self.buffer.push(byte);
+
+                if self.buffer.len() > MAX_EVENT_SIZE {
+                    self.buffer = vec![];
+                    self.state = ReaderState::Resync;
+
+                    return Some(Err(Error::Protocol(format!(
+                        "Click event longer than {} bytes, skipping the rest of the line",
+                        MAX_EVENT_SIZE
+                    ))));
+                }
+
+                if *in_string {
+                    match byte {
+                        _ if *escaped => *escaped = false,
+                        b'\\' => *escaped = true,
+                        b'"' => *in_string = false,
+                        _ => {}
+                    }
+                } else {
+                    match byte {
+                        b'"' => *in_string = true,
+                        b'{' | b'[' => *depth += 1,
+                        b'}' | b']' => *depth -= 1,
+                        _ => {}
+                    }
+                }
+
+                if *depth == 0 {
+                    self.state = ReaderState::BetweenEvents;
+                    return Some(Ok(std::mem::take(&mut self.buffer)));
+                }
+            }
+            ReaderState::Resync => {
+                if byte == b'\n' {
+                    self.state = ReaderState::BetweenEvents;
+                }
+            }
+            ReaderState::AfterArray => {}
+        }
+
+        None
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<ClickEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let ReaderState::AfterArray = self.state {
+                return None;
+            }
+
+            match self.bytes.next()? {
+                Ok(byte) => {
+                    if let Some(raw) = self.push(byte) {
+                        return Some(raw.and_then(|raw| read_event(&raw)));
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to read click events: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &str) -> Vec<Result<ClickEvent, Error>> {
+        EventReader::new(input.as_bytes()).collect()
+    }
+
+    fn instances(input: &str) -> Vec<String> {
+        read_all(input)
+            .into_iter()
+            .map(|event| event.unwrap().instance().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn reads_events_one_per_line() {
+        let input = "[\n\
+                     {\"instance\":\"a\",\"button\":1}\n\
+                     ,{\"instance\":\"b\",\"button\":3}\n";
+
+        assert_eq!(instances(input), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn reads_event_on_the_line_of_the_opening_bracket() {
+        let input = "[{\"instance\":\"a\",\"button\":1},\n{\"instance\":\"b\",\"button\":2}";
+
+        assert_eq!(instances(input), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn reads_events_split_across_lines() {
+        let input = "[\n{\n  \"instance\": \"a\",\n  \"button\": 1\n}\n]";
+
+        assert_eq!(instances(input), vec!["a"]);
+    }
+
+    #[test]
+    fn ignores_braces_and_quotes_inside_strings() {
+        let input =
+            r#"[{"name":"}{ \"]\\","instance":"a","button":1},{"instance":"b","button":1}]"#;
+        let events = read_all(input);
+
+        assert_eq!(events.len(), 2);
+        let first = events[0].as_ref().unwrap();
+        assert_eq!(first.name().map(String::as_str), Some("}{ \"]\\"));
+        assert_eq!(events[1].as_ref().unwrap().instance(), "b");
+    }
+
+    #[test]
+    fn skips_nested_objects() {
+        let input = r#"[{"instance":"a","button":4,"extra":{"x":[1,{"y":2}]}}]"#;
+        let events = read_all(input);
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0].as_ref().unwrap().button(),
+            MouseButton::ScrollUp
+        ));
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_array() {
+        let input = r#"[{"instance":"a","button":1}] {"instance":"b","button":1}"#;
+
+        assert_eq!(instances(input), vec!["a"]);
+    }
+
+    #[test]
+    fn reads_modifiers() {
+        let input = r#"[{"instance":"a","button":1,"modifiers":["Shift","Mod4","Hyper"]}]"#;
+        let events = read_all(input);
+
+        assert_eq!(
+            events[0].as_ref().unwrap().modifiers(),
+            &[Modifier::Shift, Modifier::Mod4]
+        );
+    }
+
+    #[test]
+    fn skips_the_line_of_an_event_that_is_too_long() {
+        let input = format!(
+            "[{{\"instance\":\"a\",\"button\":1,\"name\":\"{}\n\
+             ,{{\"instance\":\"b\",\"button\":1}}]",
+            "x".repeat(MAX_EVENT_SIZE)
+        );
+        let events = read_all(&input);
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].is_err());
+        assert_eq!(events[1].as_ref().unwrap().instance(), "b");
+    }
+
+    #[test]
+    fn reports_invalid_events_and_goes_on() {
+        let input = r#"[{"button":1},{"instance":"a","button":99},{"instance":"b","button":1}]"#;
+        let events = read_all(input);
+
+        assert_eq!(events.len(), 3);
+        assert!(events[0].is_err());
+        assert!(events[1].is_err());
+        assert_eq!(events[2].as_ref().unwrap().instance(), "b");
+    }
+}
@@ -0,0 +1,112 @@
+use crate::block::{ClickEvent, MouseButton};
+use crate::error::Error;
+use crate::output::{join_blocks, ClickEvents, Output};
+use crate::scheduler::RenderedBlock;
+use std::io::{BufRead, BufReader};
+
+/// Buttons that get a click area around every block.
+const CLICKABLE_BUTTONS: [u32; 5] = [1, 2, 3, 4, 5];
+
+/// Formatting for lemonbar. Click areas make lemonbar print `<button> <block name>`
+/// lines, which are expected back on our stdin, e.g. through a fifo:
+/// `stsbr < fifo | lemonbar > fifo`.
+pub struct Lemonbar {}
+
+impl Output for Lemonbar {
+    fn status_line(&self, blocks: &[RenderedBlock]) -> String {
+        let texts: Vec<String> = blocks.iter().map(format_block).collect();
+
+        format!("%{{r}}{}", join_blocks(blocks, &texts, " | ", escape))
+    }
+
+    fn click_events(&self) -> Option<ClickEvents> {
+        let lines = BufReader::new(std::io::stdin()).lines();
+
+        Some(Box::new(
+            lines.map_while(Result::ok).map(|line| read_event(&line)),
+        ))
+    }
+}
+
+impl Lemonbar {
+    pub fn new() -> Self {
+        Lemonbar {}
+    }
+}
+
+fn format_block(block: &RenderedBlock) -> String {
+    let state = block.state;
+    let mut text = escape(&state.text().to_plain_text());
+    let command_name = block.name.replace(':', "\\:");
+
+    if let Some(color) = state.color() {
+        text = format!("%{{F{}}}{}%{{F-}}", color, text);
+    }
+
+    if let Some(background) = state.background() {
+        text = format!("%{{B{}}}{}%{{B-}}", background, text);
+    }
+
+    for button in CLICKABLE_BUTTONS.iter() {
+        text = format!(
+            "%{{A{}:{} {}:}}{}%{{A}}",
+            button, button, command_name, text
+        );
+    }
+
+    text
+}
+
+/// lemonbar would interpret `%` in the text as the start of a format block.
+fn escape(text: &str) -> String {
+    text.replace('%', "%%")
+}
+
+fn read_event(line: &str) -> Result<ClickEvent, Error> {
+    let invalid = || Error::Protocol(format!("Invalid click event {:?}", line));
+    let (button, name) = line.trim_end().split_once(' ').ok_or_else(invalid)?;
+    let button = button
+        .parse()
+        .ok()
+        .and_then(MouseButton::from_code)
+        .ok_or_else(invalid)?;
+
+    Ok(ClickEvent::new(button, name.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockState;
+
+    #[test]
+    fn reads_click_events() {
+        let event = read_event("3 volume_2\n").unwrap();
+
+        assert_eq!(event.button(), MouseButton::Right);
+        assert_eq!(event.instance(), "volume_2");
+    }
+
+    #[test]
+    fn rejects_invalid_click_events() {
+        for line in &["", "3", "x volume", "0 volume", "10 volume"] {
+            assert!(read_event(line).is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn escapes_text_and_separators() {
+        let state = BlockState::new("50%".into());
+        let block = |separator| RenderedBlock {
+            name: "volume",
+            module: "volume",
+            state: &state,
+            separator,
+        };
+        let blocks = [block(Some("<b>%</b>")), block(None)];
+        let line = Lemonbar::new().status_line(&blocks);
+        let text = "%{A5:5 volume:}%{A4:4 volume:}%{A3:3 volume:}%{A2:2 volume:}%{A1:1 volume:}50%%%{A}%{A}%{A}%{A}%{A}";
+
+        assert_eq!(line, format!("%{{r}}{}%%{}", text, text));
+    }
+}
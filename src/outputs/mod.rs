@@ -0,0 +1,4 @@
+pub mod i3bar;
+pub mod lemonbar;
+pub mod plain;
+pub mod tmux;
@@ -0,0 +1,22 @@
+use crate::output::{join_blocks, Output};
+use crate::scheduler::RenderedBlock;
+
+/// Plain text, one status line per update, for debugging blocks in a terminal.
+pub struct Plain {}
+
+impl Output for Plain {
+    fn status_line(&self, blocks: &[RenderedBlock]) -> String {
+        let texts: Vec<String> = blocks
+            .iter()
+            .map(|block| block.state.text().to_plain_text())
+            .collect();
+
+        join_blocks(blocks, &texts, " | ", str::to_owned)
+    }
+}
+
+impl Plain {
+    pub fn new() -> Self {
+        Plain {}
+    }
+}
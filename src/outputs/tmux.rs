@@ -0,0 +1,47 @@
+use crate::block::BlockState;
+use crate::output::{join_blocks, Output};
+use crate::scheduler::RenderedBlock;
+
+/// A line for tmux's `status-right`, e.g. `#(stsbr)` with `output = "tmux"`.
+pub struct Tmux {}
+
+impl Output for Tmux {
+    fn status_line(&self, blocks: &[RenderedBlock]) -> String {
+        let texts: Vec<String> = blocks
+            .iter()
+            .map(|block| format_block(block.state))
+            .collect();
+
+        join_blocks(blocks, &texts, " | ", escape)
+    }
+}
+
+impl Tmux {
+    pub fn new() -> Self {
+        Tmux {}
+    }
+}
+
+fn format_block(state: &BlockState) -> String {
+    let text = escape(&state.text().to_plain_text());
+    let mut style = vec![];
+
+    if let Some(color) = state.color() {
+        style.push(format!("fg={}", color));
+    }
+
+    if let Some(background) = state.background() {
+        style.push(format!("bg={}", background));
+    }
+
+    if style.is_empty() {
+        text
+    } else {
+        format!("#[{}]{}#[default]", style.join(","), text)
+    }
+}
+
+/// tmux would interpret `#` sequences in the text as formats.
+fn escape(text: &str) -> String {
+    text.replace('#', "##")
+}
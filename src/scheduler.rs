@@ -0,0 +1,263 @@
+use crate::block::{Block, BlockState, ClickEvent, Layout};
+use crate::click::{spawn_command, ClickAction, ClickBindings, REFRESH_ACTION};
+use crate::control::{ControlRequest, Reply};
+use crate::theme::Theme;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+pub enum Message {
+    Click(ClickEvent),
+    Update(String),
+    Stop,
+    Continue,
+    Reload,
+    /// A request from the control socket, answered through the sender.
+    Control(ControlRequest, Sender<Reply>),
+}
+
+pub struct ScheduledBlock {
+    name: String,
+    module: String,
+    block: Box<dyn Block>,
+    interval: Option<Duration>,
+    next_update: Option<Instant>,
+    state: Option<BlockState>,
+    theme: Theme,
+    layout: Layout,
+    bindings: ClickBindings,
+}
+
+/// A block with something to show, as handed to the output.
+pub struct RenderedBlock<'a> {
+    /// Unique among the blocks on the bar and stable across reloads.
+    pub name: &'a str,
+    pub module: &'a str,
+    pub state: &'a BlockState,
+    /// Drawn after the block instead of the bar's own separator.
+    pub separator: Option<&'a str>,
+}
+
+pub struct Scheduler {
+    blocks: Vec<ScheduledBlock>,
+    paused: bool,
+    blocks_replaced: bool,
+}
+
+impl ScheduledBlock {
+    /// `interval` overrides the block's own refresh interval when set.
+    pub fn new(
+        name: String,
+        module: String,
+        block: Box<dyn Block>,
+        interval: Option<Duration>,
+    ) -> Self {
+        ScheduledBlock {
+            name,
+            module,
+            block,
+            interval,
+            next_update: Some(Instant::now()),
+            state: None,
+            theme: Theme::default(),
+            layout: Layout::default(),
+            bindings: ClickBindings::default(),
+        }
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_bindings(mut self, bindings: ClickBindings) -> Self {
+        self.bindings = bindings;
+        self
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.next_update.is_some_and(|next_update| next_update <= now)
+    }
+
+    fn refresh(&mut self, now: Instant) -> bool {
+        let state = match self.block.current_state() {
+            Ok(state) => Some(self.theme.apply(self.layout.apply(state))),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        };
+
+        self.next_update = self
+            .interval
+            .or_else(|| self.block.refresh_interval())
+            .and_then(|interval| now.checked_add(interval));
+
+        if state != self.state {
+            self.state = state;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new(blocks: Vec<ScheduledBlock>) -> Self {
+        Scheduler {
+            blocks,
+            paused: false,
+            blocks_replaced: false,
+        }
+    }
+
+    /// Swaps in a new set of blocks, e.g. after the configuration was reloaded.
+    pub fn replace_blocks(&mut self, mut blocks: Vec<ScheduledBlock>) {
+        if self.paused {
+            for block in blocks.iter_mut() {
+                block.block.pause();
+            }
+        }
+
+        self.blocks = blocks;
+        self.blocks_replaced = true;
+    }
+
+    /// Stops refreshing blocks until `resume` is called.
+    pub fn pause(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        self.paused = true;
+
+        for block in self.blocks.iter_mut() {
+            block.block.pause();
+        }
+    }
+
+    /// Resumes refreshing and brings every block up to date immediately.
+    pub fn resume(&mut self) {
+        if !self.paused {
+            return;
+        }
+
+        self.paused = false;
+
+        let now = Instant::now();
+
+        for block in self.blocks.iter_mut() {
+            block.block.resume();
+            block.next_update = Some(now);
+        }
+    }
+
+    fn find(&mut self, name: &str) -> Option<&mut ScheduledBlock> {
+        self.blocks.iter_mut().find(|block| block.name == name)
+    }
+
+    pub fn invalidate(&mut self, name: &str) {
+        match self.find(name) {
+            Some(block) => block.next_update = Some(Instant::now()),
+            None => warn!("Update requested for unknown block {}", name),
+        }
+    }
+
+    /// Brings every block up to date on the next update.
+    pub fn invalidate_all(&mut self) {
+        let now = Instant::now();
+
+        for block in self.blocks.iter_mut() {
+            block.next_update = Some(now);
+        }
+    }
+
+    /// Runs an action of a block, as if it was bound to a click.
+    pub fn run_action(&mut self, name: &str, action: &str) -> Result<(), String> {
+        let block = self
+            .find(name)
+            .ok_or_else(|| format!("no block named `{}`", name))?;
+
+        if action != REFRESH_ACTION {
+            if !block.block.actions().contains(&action) {
+                return Err(format!("block `{}` has no action `{}`", name, action));
+            }
+
+            block.block.run_action(action);
+        }
+
+        block.next_update = Some(Instant::now());
+
+        Ok(())
+    }
+
+    pub fn handle_click(&mut self, event: ClickEvent) {
+        let name = event.instance().to_owned();
+
+        match self.find(&name) {
+            Some(block) => {
+                match block.bindings.find(&event) {
+                    Some(ClickAction::Command(command)) => spawn_command(command),
+                    Some(ClickAction::Builtin(action)) if action == REFRESH_ACTION => {}
+                    Some(ClickAction::Builtin(action)) => block.block.run_action(action),
+                    None => block.block.handle_click(event),
+                }
+
+                block.next_update = Some(Instant::now());
+            }
+            None => warn!("Click on unknown block {}", name),
+        }
+    }
+
+    /// Refreshes every block whose interval has passed or which asked for an update.
+    /// Returns whether the output of any of them changed.
+    pub fn update(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+
+        let now = Instant::now();
+        let mut changed = self.blocks_replaced;
+        self.blocks_replaced = false;
+
+        for block in self.blocks.iter_mut().filter(|block| block.is_due(now)) {
+            changed |= block.refresh(now);
+        }
+
+        changed
+    }
+
+    /// How long the main loop can sleep before some block needs to be refreshed,
+    /// or `None` if all of them only update on notification or the bar is paused.
+    pub fn time_until_next_update(&self) -> Option<Duration> {
+        if self.paused {
+            return None;
+        }
+
+        let now = Instant::now();
+
+        self.blocks
+            .iter()
+            .filter_map(|block| block.next_update)
+            .min()
+            .map(|next_update| next_update.saturating_duration_since(now))
+    }
+
+    pub fn rendered_blocks(&self) -> Vec<RenderedBlock<'_>> {
+        self.blocks
+            .iter()
+            .filter_map(|block| {
+                block.state.as_ref().map(|state| RenderedBlock {
+                    name: &block.name,
+                    module: &block.module,
+                    state,
+                    separator: block.theme.separator.as_deref(),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,50 @@
+use crate::scheduler::Message;
+use nix::sys::signal::{SigSet, Signal};
+use std::sync::mpsc::Sender;
+
+/// Sent by i3bar when the bar gets hidden, instead of the default SIGSTOP.
+pub const STOP_SIGNAL: Signal = Signal::SIGUSR1;
+/// Sent by i3bar when the bar becomes visible again.
+pub const CONT_SIGNAL: Signal = Signal::SIGUSR2;
+/// Reloads the configuration.
+pub const RELOAD_SIGNAL: Signal = Signal::SIGHUP;
+
+fn handled_signals() -> SigSet {
+    let mut signals = SigSet::empty();
+
+    signals.add(STOP_SIGNAL);
+    signals.add(CONT_SIGNAL);
+    signals.add(RELOAD_SIGNAL);
+
+    signals
+}
+
+/// Blocks the handled signals and forwards them to the main loop from a
+/// dedicated thread. Has to be called before any other thread is spawned, so
+/// that they all inherit the signal mask.
+pub fn create_signal_thread(sender: Sender<Message>) -> nix::Result<()> {
+    let signals = handled_signals();
+    signals.thread_block()?;
+
+    std::thread::spawn(move || loop {
+        let message = match signals.wait() {
+            Ok(STOP_SIGNAL) => Message::Stop,
+            Ok(CONT_SIGNAL) => Message::Continue,
+            Ok(RELOAD_SIGNAL) => Message::Reload,
+            Ok(signal) => {
+                warn!("Unexpected signal {:?}", signal);
+                continue;
+            }
+            Err(e) => {
+                error!("Failed to wait for signals: {}", e);
+                break;
+            }
+        };
+
+        if sender.send(message).is_err() {
+            break;
+        }
+    });
+
+    Ok(())
+}
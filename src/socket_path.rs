@@ -0,0 +1,13 @@
+//! Where the control socket is, shared by stsbr and stsbr-msg.
+
+use std::env;
+use std::path::PathBuf;
+
+/// `$XDG_RUNTIME_DIR/stsbr.sock`. Without a runtime directory there is no
+/// default, as anyone could take a fixed name in the temporary directory first.
+pub fn default_socket_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .map(|dir| dir.join("stsbr.sock"))
+}
@@ -0,0 +1,399 @@
+use crate::block::{BlockState, Severity};
+use crate::cli::expand_home;
+use crate::error::Error;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a block looks like in one severity.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateStyle {
+    pub color: Option<String>,
+    pub background: Option<String>,
+    pub urgent: bool,
+}
+
+impl StateStyle {
+    fn colored(color: &str) -> Self {
+        StateStyle {
+            color: Some(color.to_owned()),
+            ..StateStyle::default()
+        }
+    }
+
+    fn urgent(self) -> Self {
+        StateStyle {
+            urgent: true,
+            ..self
+        }
+    }
+}
+
+/// Turns the severity blocks report into colors, and separates blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub idle: StateStyle,
+    pub info: StateStyle,
+    pub good: StateStyle,
+    pub warning: StateStyle,
+    pub critical: StateStyle,
+    /// Drawn between blocks instead of the bar's own separator.
+    pub separator: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::solarized()
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "solarized" => Some(Theme::solarized()),
+            "gruvbox" => Some(Theme::gruvbox()),
+            "plain" => Some(Theme::plain()),
+            _ => None,
+        }
+    }
+
+    fn solarized() -> Self {
+        Theme {
+            idle: StateStyle::default(),
+            info: StateStyle::colored("#268bd2"),
+            good: StateStyle::colored("#859900"),
+            warning: StateStyle::colored("#b58900"),
+            critical: StateStyle::colored("#dc322f").urgent(),
+            separator: None,
+        }
+    }
+
+    fn gruvbox() -> Self {
+        Theme {
+            idle: StateStyle::default(),
+            info: StateStyle::colored("#83a598"),
+            good: StateStyle::colored("#b8bb26"),
+            warning: StateStyle::colored("#fabd2f"),
+            critical: StateStyle::colored("#fb4934").urgent(),
+            separator: None,
+        }
+    }
+
+    /// No colors at all, critical blocks are only marked urgent.
+    fn plain() -> Self {
+        Theme {
+            idle: StateStyle::default(),
+            info: StateStyle::default(),
+            good: StateStyle::default(),
+            warning: StateStyle::default(),
+            critical: StateStyle::default().urgent(),
+            separator: None,
+        }
+    }
+
+    pub fn style(&self, severity: Severity) -> &StateStyle {
+        match severity {
+            Severity::Idle => &self.idle,
+            Severity::Info => &self.info,
+            Severity::Good => &self.good,
+            Severity::Warning => &self.warning,
+            Severity::Critical => &self.critical,
+        }
+    }
+
+    fn style_mut(&mut self, severity: Severity) -> &mut StateStyle {
+        match severity {
+            Severity::Idle => &mut self.idle,
+            Severity::Info => &mut self.info,
+            Severity::Good => &mut self.good,
+            Severity::Warning => &mut self.warning,
+            Severity::Critical => &mut self.critical,
+        }
+    }
+
+    /// Fills in the colors of the state's severity, keeping those the block
+    /// has set itself.
+    pub fn apply(&self, mut state: BlockState) -> BlockState {
+        let style = self.style(state.severity());
+
+        if let (None, Some(color)) = (state.color(), &style.color) {
+            state = state.with_color(color.clone());
+        }
+
+        if let (None, Some(background)) = (state.background(), &style.background) {
+            state = state.with_background(background.clone());
+        }
+
+        if state.urgent().is_none() && style.urgent {
+            state = state.with_urgent(true);
+        }
+
+        state
+    }
+
+    /// Derives a theme from this one. Settings are applied in order: the
+    /// built-in theme, imported palettes, and then the individual settings.
+    pub fn configure(&self, config: &ThemeConfig) -> Result<Theme, Error> {
+        let mut theme = match &config.name {
+            Some(name) => Theme::builtin(name).ok_or_else(|| {
+                Error::config_key(
+                    "name",
+                    format!(
+                        "unknown theme `{}`, expected one of solarized, gruvbox or plain",
+                        name
+                    ),
+                )
+            })?,
+            None => self.clone(),
+        };
+
+        if let Some(path) = &config.xresources {
+            theme.import(&read_xresources(&read_palette_file(path, "xresources")?));
+        }
+
+        if let Some(path) = &config.base16 {
+            let palette = read_base16(&read_palette_file(path, "base16")?);
+
+            if palette.is_empty() {
+                return Err(Error::config_key(
+                    "base16",
+                    format!("no base16 colors found in {}", path.display()),
+                ));
+            }
+
+            theme.import(&palette);
+        }
+
+        let styles = [
+            (Severity::Idle, &config.idle),
+            (Severity::Info, &config.info),
+            (Severity::Good, &config.good),
+            (Severity::Warning, &config.warning),
+            (Severity::Critical, &config.critical),
+        ];
+
+        for (severity, style) in styles.iter() {
+            let target = theme.style_mut(*severity);
+
+            if let Some(color) = &style.color {
+                target.color = Some(color.clone());
+            }
+
+            if let Some(background) = &style.background {
+                target.background = Some(background.clone());
+            }
+
+            if let Some(urgent) = style.urgent {
+                target.urgent = urgent;
+            }
+        }
+
+        if let Some(separator) = &config.separator {
+            theme.separator = Some(separator.clone());
+        }
+
+        Ok(theme)
+    }
+
+    fn import(&mut self, palette: &Palette) {
+        let colors = [
+            (Severity::Info, &palette.blue),
+            (Severity::Good, &palette.green),
+            (Severity::Warning, &palette.yellow),
+            (Severity::Critical, &palette.red),
+        ];
+
+        for (severity, color) in colors.iter() {
+            if let Some(color) = color {
+                self.style_mut(*severity).color = Some(color.clone());
+            }
+        }
+    }
+}
+
+/// The `[theme]` section, or the `theme` table of a single block.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// One of the built-in themes to start from.
+    name: Option<String>,
+    /// Takes the terminal colors from an X resources file.
+    xresources: Option<PathBuf>,
+    /// Takes the colors from a base16 scheme.
+    base16: Option<PathBuf>,
+    separator: Option<String>,
+    idle: StyleConfig,
+    info: StyleConfig,
+    good: StyleConfig,
+    warning: StyleConfig,
+    critical: StyleConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StyleConfig {
+    color: Option<String>,
+    background: Option<String>,
+    urgent: Option<bool>,
+}
+
+/// The colors the severities are mapped to when importing a color scheme.
+#[derive(Debug, Default)]
+struct Palette {
+    red: Option<String>,
+    green: Option<String>,
+    yellow: Option<String>,
+    blue: Option<String>,
+}
+
+impl Palette {
+    fn set(&mut self, name: &str, color: String) {
+        match name {
+            "red" => self.red = Some(color),
+            "green" => self.green = Some(color),
+            "yellow" => self.yellow = Some(color),
+            "blue" => self.blue = Some(color),
+            _ => {}
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.red.is_none() && self.green.is_none() && self.yellow.is_none() && self.blue.is_none()
+    }
+}
+
+fn read_palette_file(path: &Path, key: &str) -> Result<String, Error> {
+    let path = expand_home(path);
+
+    fs::read_to_string(&path)
+        .map_err(|e| Error::config_key(key, format!("Cannot read {}: {}", path.display(), e)))
+}
+
+/// Reads `color1` to `color4` of lines like `*.color1: #dc322f`.
+fn read_xresources(contents: &str) -> Palette {
+    let mut palette = Palette::default();
+
+    for line in contents.lines().map(str::trim) {
+        // `!` starts a comment, `#` a preprocessor directive.
+        if line.starts_with('!') || line.starts_with('#') {
+            continue;
+        }
+
+        let (resource, value) = match line.split_once(':') {
+            Some(resource) => resource,
+            None => continue,
+        };
+        let name = resource.trim().rsplit(['.', '*']).next().unwrap_or("");
+        let color = match name {
+            "color1" => "red",
+            "color2" => "green",
+            "color3" => "yellow",
+            "color4" => "blue",
+            _ => continue,
+        };
+
+        palette.set(color, value.trim().to_owned());
+    }
+
+    palette
+}
+
+/// Reads the base16 accents of lines like `base08: "dc322f"` or, in newer
+/// schemes, `base08: "#dc322f"  # red`.
+fn read_base16(contents: &str) -> Palette {
+    let mut palette = Palette::default();
+
+    for line in contents.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let color = match key.trim() {
+            "base08" => "red",
+            "base0B" => "green",
+            "base0A" => "yellow",
+            "base0D" => "blue",
+            _ => continue,
+        };
+
+        if let Some(value) = base16_color(value) {
+            palette.set(color, format!("#{}", value));
+        }
+    }
+
+    palette
+}
+
+/// The hex digits of a YAML value, quoted or not, with an optional comment.
+fn base16_color(value: &str) -> Option<&str> {
+    let value = value.trim();
+    let value = match value.chars().next() {
+        Some(quote @ '"') | Some(quote @ '\'') => value[1..].split(quote).next()?,
+        // Only `#` after whitespace starts a comment, here the value is
+        // unquoted.
+        _ => value
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or(""),
+    };
+    let digits = value.trim().trim_start_matches('#');
+
+    if digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(digits)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_xresources() {
+        let palette = read_xresources(
+            "! solarized\n\
+             #define S_red #dc322f\n\
+             *.color1: #dc322f\n\
+             URxvt*color2:   #859900\n\
+             *color3 : #b58900\n\
+             *.color12: #268bd2\n",
+        );
+
+        assert_eq!(palette.red.as_deref(), Some("#dc322f"));
+        assert_eq!(palette.green.as_deref(), Some("#859900"));
+        assert_eq!(palette.yellow.as_deref(), Some("#b58900"));
+        assert_eq!(palette.blue, None);
+    }
+
+    #[test]
+    fn reads_base16() {
+        let palette = read_base16(
+            "scheme: \"Solarized Dark\"\n\
+             base08: \"dc322f\"\n\
+             base0B: '#859900'  # green\n\
+             base0A: #b58900\n\
+             base0D: \"268bd2x\"\n",
+        );
+
+        assert_eq!(palette.red.as_deref(), Some("#dc322f"));
+        assert_eq!(palette.green.as_deref(), Some("#859900"));
+        assert_eq!(palette.yellow.as_deref(), Some("#b58900"));
+        assert_eq!(palette.blue, None);
+    }
+
+    #[test]
+    fn imports_palettes_into_the_severities() {
+        let mut theme = Theme::plain();
+        theme.import(&read_base16("base08: \"dc322f\"\n"));
+
+        assert_eq!(
+            theme.style(Severity::Critical).color.as_deref(),
+            Some("#dc322f")
+        );
+        assert_eq!(
+            theme.style(Severity::Warning),
+            Theme::plain().style(Severity::Warning)
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::scheduler::Message;
+use std::ffi::{CString, OsStr};
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+/// Asks for a reload whenever the config file is written. The directory is
+/// watched rather than the file itself, since many editors save by replacing
+/// the file with a new one.
+pub fn create_config_watcher(path: &Path, sender: Sender<Message>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+        .to_owned();
+    let dir = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Owning the descriptor closes it on every return path.
+    let mut inotify = unsafe { File::from_raw_fd(fd) };
+    let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+
+    if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            let length = match inotify.read(&mut buffer) {
+                Ok(length) => length,
+                Err(e) => {
+                    error!("Stopped watching the config file: {}", e);
+                    break;
+                }
+            };
+
+            let changed = changed_names(&buffer[..length])
+                .iter()
+                .any(|name| name == &file_name);
+
+            if changed && sender.send(Message::Reload).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Extracts the names of the changed files from a buffer of `inotify_event`s.
+fn changed_names(buffer: &[u8]) -> Vec<&OsStr> {
+    let header_size = size_of::<libc::inotify_event>();
+    let mut names = vec![];
+    let mut offset = 0;
+
+    while offset + header_size <= buffer.len() {
+        let event = unsafe {
+            std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::inotify_event)
+        };
+        let name_start = offset + header_size;
+        let name_end = (name_start + event.len as usize).min(buffer.len());
+        // The name is padded with NUL bytes up to `len`.
+        let name = &buffer[name_start..name_end];
+        let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+
+        names.push(OsStr::from_bytes(name));
+        offset = name_end;
+    }
+
+    names
+}